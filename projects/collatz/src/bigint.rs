@@ -0,0 +1,252 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// An arbitrary-precision unsigned integer.
+///
+/// Only the handful of operations the Collatz map needs are implemented:
+/// multiply by a small number and add a small number, divide by a small
+/// number, comparisons, and conversion to and from decimal text.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    // Base 2^32 digits, least significant first, with no trailing zeros.
+    // Zero is the empty vector.
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        Self { limbs: Vec::new() }
+    }
+
+    /// 2^exp
+    pub fn pow2(exp: u32) -> Self {
+        let mut limbs = vec![0u32; (exp / 32) as usize + 1];
+        limbs[(exp / 32) as usize] = 1 << (exp % 32);
+        Self { limbs }
+    }
+
+    /// base^exp
+    pub fn pow_small(base: u32, exp: u32) -> Self {
+        let mut value = BigUint::from(1u32);
+        for _ in 0..exp {
+            value.mul_add_small(base, 0);
+        }
+        value
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.limbs.first().is_none_or(|limb| limb % 2 == 0)
+    }
+
+    /// The value as a u128, or None if it doesn't fit.
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.limbs.len() > 4 {
+            return None;
+        }
+        Some(
+            self.limbs
+                .iter()
+                .rev()
+                .fold(0u128, |acc, &limb| (acc << 32) | limb as u128),
+        )
+    }
+
+    /// self = self * mul + add
+    pub fn mul_add_small(&mut self, mul: u32, add: u32) {
+        let mut carry = add as u64;
+        for limb in self.limbs.iter_mut() {
+            let value = *limb as u64 * mul as u64 + carry;
+            *limb = value as u32;
+            carry = value >> 32;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+        self.trim();
+    }
+
+    /// self = self - sub. Panics if the result would be negative.
+    pub fn sub_small(&mut self, sub: u32) {
+        let mut borrow = sub as u64;
+        for limb in self.limbs.iter_mut() {
+            if borrow == 0 {
+                break;
+            }
+            let (value, under) = (*limb as u64).overflowing_sub(borrow);
+            *limb = value as u32;
+            borrow = under as u64;
+        }
+        assert!(borrow == 0, "subtraction underflow");
+        self.trim();
+    }
+
+    /// self % div, without changing self. Panics if div is 0.
+    pub fn rem_small(&self, div: u32) -> u32 {
+        assert!(div != 0, "division by zero");
+        let mut rem = 0u64;
+        for limb in self.limbs.iter().rev() {
+            rem = ((rem << 32) | *limb as u64) % div as u64;
+        }
+        rem as u32
+    }
+
+    /// self = self / div, returning the remainder. Panics if div is 0.
+    pub fn div_rem_small(&mut self, div: u32) -> u32 {
+        assert!(div != 0, "division by zero");
+        let mut rem = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let value = (rem << 32) | *limb as u64;
+            *limb = (value / div as u64) as u32;
+            rem = value % div as u64;
+        }
+        self.trim();
+        rem as u32
+    }
+
+    fn trim(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+}
+
+impl From<u128> for BigUint {
+    fn from(mut value: u128) -> Self {
+        let mut limbs = Vec::new();
+        while value > 0 {
+            limbs.push(value as u32);
+            value >>= 32;
+        }
+        Self { limbs }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        Self::from(value as u128)
+    }
+}
+
+impl From<u32> for BigUint {
+    fn from(value: u32) -> Self {
+        Self::from(value as u128)
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // With no trailing zeros, more limbs always means a bigger number
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return f.pad_integral(true, "", "0");
+        }
+
+        // Peel off nine decimal digits at a time
+        let mut rest = self.clone();
+        let mut chunks = Vec::new();
+        while !rest.is_zero() {
+            chunks.push(rest.div_rem_small(1_000_000_000));
+        }
+
+        let mut digits = chunks.pop().unwrap_or(0).to_string();
+        for chunk in chunks.iter().rev() {
+            digits.push_str(&format!("{chunk:09}"));
+        }
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseBigUintError;
+
+impl fmt::Display for ParseBigUintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid digit found in string")
+    }
+}
+
+impl std::error::Error for ParseBigUintError {}
+
+impl FromStr for BigUint {
+    type Err = ParseBigUintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ParseBigUintError);
+        }
+        let mut value = BigUint::zero();
+        for c in s.chars() {
+            let digit = c.to_digit(10).ok_or(ParseBigUintError)?;
+            value.mul_add_small(10, digit);
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_u128() {
+        for value in [0u128, 1, 2, 1 << 32, u64::MAX as u128 + 7, u128::MAX] {
+            assert_eq!(BigUint::from(value).to_u128(), Some(value));
+        }
+        let mut big = BigUint::from(u128::MAX);
+        big.mul_add_small(3, 1);
+        assert_eq!(big.to_u128(), None);
+    }
+
+    #[test]
+    fn decimal_text() {
+        let text = "340282366920938463463374607431768211456"; // 2^128
+        let value: BigUint = text.parse().unwrap();
+        assert_eq!(value, BigUint::pow2(128));
+        assert_eq!(value.to_string(), text);
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::from(1_000_000_000u32).to_string(), "1000000000");
+        assert!("12a".parse::<BigUint>().is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut value = BigUint::pow2(200);
+        assert!(value.is_even());
+        assert_eq!(value.div_rem_small(2), 0);
+        assert_eq!(value, BigUint::pow2(199));
+
+        value.mul_add_small(3, 1);
+        assert!(!value.is_even());
+        assert_eq!(value.div_rem_small(3), 1);
+        assert_eq!(value, BigUint::pow2(199));
+        assert!(BigUint::pow2(199) > BigUint::from(u128::MAX));
+
+        assert_eq!(BigUint::pow_small(2, 150), BigUint::pow2(150));
+        assert_eq!(BigUint::pow_small(3, 4), BigUint::from(81u32));
+        assert_eq!(BigUint::pow_small(3, 100).rem_small(3), 0);
+
+        let mut value = BigUint::pow2(128);
+        value.sub_small(1);
+        assert_eq!(value, BigUint::from(u128::MAX));
+        assert_eq!(value.rem_small(10), 5);
+    }
+}
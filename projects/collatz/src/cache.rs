@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Memory budget the command line and first_with_path_length use by default.
+pub const DEFAULT_CACHE_MB: usize = 256;
+
+/// Number of independently locked pieces of a BoundedMap.
+const SHARDS: usize = 64;
+
+/// Rough memory use of one BoundedMap entry: the u128 key, the u32 length,
+/// padding and the hash table's own overhead.
+const MAP_ENTRY_BYTES: usize = 40;
+
+/// Memory use of one DenseCache entry.
+const DENSE_ENTRY_BYTES: usize = 4;
+
+/// Somewhere to remember path lengths (start -> length) during a search.
+///
+/// Caches are shared between search threads, so they take &self and do their
+/// own locking. A cache may forget entries to stay within its memory budget,
+/// except for 1, whose length must always be known so that every walk ends.
+pub trait LengthCache: Sync {
+    fn get(&self, n: u128) -> Option<u32>;
+
+    fn insert(&self, n: u128, len: u32);
+
+    fn stats(&self) -> CacheStats;
+
+    /// Call visit with every entry currently held, in no particular order.
+    fn for_each_entry(&self, visit: &mut dyn FnMut(u128, u32));
+}
+
+/// Counters a cache keeps about itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: u64,
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups that found a length, 0.0 before any lookups.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl std::ops::Add for CacheStats {
+    type Output = CacheStats;
+
+    fn add(self, other: CacheStats) -> CacheStats {
+        CacheStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            entries: self.entries + other.entries,
+            evictions: self.evictions + other.evictions,
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} entries, {} evictions",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.entries,
+            self.evictions
+        )
+    }
+}
+
+/// Hit and miss counters shared by the cache implementations.
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Counters {
+    fn count(&self, found: Option<u32>) -> Option<u32> {
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+}
+
+/// A flat array indexed by the value itself, for 0..size. No hashing and no
+/// locking, which suits the small values every path runs through.
+pub struct DenseCache {
+    // 0 means unknown, since every real path length is at least 1
+    lengths: Vec<AtomicU32>,
+    entries: AtomicU64,
+    counters: Counters,
+}
+
+impl DenseCache {
+    /// A cache for the values below size. It always holds at least 1.
+    pub fn new(size: usize) -> DenseCache {
+        let cache = DenseCache {
+            lengths: (0..size.max(2)).map(|_| AtomicU32::new(0)).collect(),
+            entries: AtomicU64::new(0),
+            counters: Counters::default(),
+        };
+        cache.insert(1, 1);
+        cache
+    }
+
+    /// The values this cache can hold are 0..size().
+    pub fn size(&self) -> usize {
+        self.lengths.len()
+    }
+}
+
+impl LengthCache for DenseCache {
+    fn get(&self, n: u128) -> Option<u32> {
+        let found = self
+            .lengths
+            .get(usize::try_from(n).ok()?)
+            .map(|len| len.load(Ordering::Relaxed))
+            .filter(|len| *len != 0);
+        self.counters.count(found)
+    }
+
+    fn insert(&self, n: u128, len: u32) {
+        if let Some(slot) = usize::try_from(n).ok().and_then(|n| self.lengths.get(n)) {
+            if slot.swap(len, Ordering::Relaxed) == 0 {
+                self.entries.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            entries: self.entries.load(Ordering::Relaxed),
+            evictions: 0,
+        }
+    }
+
+    fn for_each_entry(&self, visit: &mut dyn FnMut(u128, u32)) {
+        for (n, len) in self.lengths.iter().enumerate() {
+            let len = len.load(Ordering::Relaxed);
+            if len != 0 {
+                visit(n as u128, len);
+            }
+        }
+    }
+}
+
+/// Two generations of entries. New entries go into current, and once it is
+/// full it becomes previous and the old previous is dropped. A hit in
+/// previous is moved back to current, so anything used recently survives.
+/// That gives least-recently-used eviction without tracking any order.
+#[derive(Default)]
+struct Generations {
+    current: HashMap<u128, u32>,
+    previous: HashMap<u128, u32>,
+}
+
+/// A hash map that holds at most a fixed number of entries, for the large
+/// values too spread out for a DenseCache. It is split into shards with their
+/// own locks so threads rarely wait on each other.
+pub struct BoundedMap {
+    shards: Vec<Mutex<Generations>>,
+    // Entries allowed in each generation of each shard
+    generation_size: usize,
+    evictions: AtomicU64,
+    counters: Counters,
+}
+
+impl BoundedMap {
+    /// A map that holds at most (about) max_entries entries.
+    pub fn new(max_entries: usize) -> BoundedMap {
+        BoundedMap {
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(Generations::default()))
+                .collect(),
+            generation_size: (max_entries / SHARDS / 2).max(1),
+            evictions: AtomicU64::new(0),
+            counters: Counters::default(),
+        }
+    }
+
+    fn shard(&self, n: u128) -> &Mutex<Generations> {
+        // Mix in high bits so runs of even numbers spread over every shard
+        &self.shards[(n ^ (n >> 17)) as usize % SHARDS]
+    }
+
+    fn insert_current(&self, shard: &mut Generations, n: u128, len: u32) {
+        if shard.current.len() >= self.generation_size {
+            let evicted = std::mem::take(&mut shard.current);
+            let evicted = std::mem::replace(&mut shard.previous, evicted);
+            self.evictions
+                .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+        }
+        shard.current.insert(n, len);
+    }
+}
+
+impl LengthCache for BoundedMap {
+    fn get(&self, n: u128) -> Option<u32> {
+        let mut shard = self.shard(n).lock().unwrap();
+        let mut found = shard.current.get(&n).copied();
+        if found.is_none() {
+            found = shard.previous.remove(&n);
+            if let Some(len) = found {
+                self.insert_current(&mut shard, n, len);
+            }
+        }
+        self.counters.count(found)
+    }
+
+    fn insert(&self, n: u128, len: u32) {
+        let mut shard = self.shard(n).lock().unwrap();
+        shard.previous.remove(&n);
+        self.insert_current(&mut shard, n, len);
+    }
+
+    fn stats(&self) -> CacheStats {
+        let entries = self
+            .shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.lock().unwrap();
+                (shard.current.len() + shard.previous.len()) as u64
+            })
+            .sum();
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            entries,
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn for_each_entry(&self, visit: &mut dyn FnMut(u128, u32)) {
+        for shard in &self.shards {
+            let shard = shard.lock().unwrap();
+            for (n, len) in shard.previous.iter().chain(shard.current.iter()) {
+                visit(*n, *len);
+            }
+        }
+    }
+}
+
+/// The default cache: a DenseCache for small values in front of a BoundedMap
+/// for everything else.
+pub struct TieredCache {
+    dense: DenseCache,
+    map: BoundedMap,
+}
+
+impl TieredCache {
+    pub fn new(dense_size: usize, max_map_entries: usize) -> TieredCache {
+        TieredCache {
+            dense: DenseCache::new(dense_size),
+            map: BoundedMap::new(max_map_entries),
+        }
+    }
+
+    /// Split a memory budget evenly between the two tiers.
+    pub fn with_megabytes(megabytes: usize) -> TieredCache {
+        let half = megabytes.saturating_mul(1 << 20) / 2;
+        TieredCache::new(half / DENSE_ENTRY_BYTES, half / MAP_ENTRY_BYTES)
+    }
+}
+
+impl LengthCache for TieredCache {
+    fn get(&self, n: u128) -> Option<u32> {
+        if n < self.dense.size() as u128 {
+            self.dense.get(n)
+        } else {
+            self.map.get(n)
+        }
+    }
+
+    fn insert(&self, n: u128, len: u32) {
+        if n < self.dense.size() as u128 {
+            self.dense.insert(n, len)
+        } else {
+            self.map.insert(n, len)
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.dense.stats() + self.map.stats()
+    }
+
+    fn for_each_entry(&self, visit: &mut dyn FnMut(u128, u32)) {
+        self.dense.for_each_entry(visit);
+        self.map.for_each_entry(visit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dense_cache_knows_one() {
+        let cache = DenseCache::new(0);
+        assert_eq!(cache.get(1), Some(1));
+        assert_eq!(cache.get(5), None);
+        cache.insert(5, 6); // out of range, ignored
+        assert_eq!(cache.get(5), None);
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().misses, 2);
+    }
+
+    #[test]
+    fn bounded_map_stays_bounded() {
+        let cache = BoundedMap::new(SHARDS * 8);
+        for n in 0..10_000u128 {
+            cache.insert(n, 1);
+        }
+        let stats = cache.stats();
+        assert!(stats.entries <= (SHARDS * 8) as u64);
+        assert_eq!(stats.entries + stats.evictions, 10_000);
+    }
+
+    #[test]
+    fn bounded_map_keeps_recently_used() {
+        let cache = BoundedMap::new(SHARDS * 8);
+        // Keep touching 0 while filling its shard many times over
+        cache.insert(0, 1);
+        for n in 1..10_000u128 {
+            cache.insert(n * SHARDS as u128, 2);
+            assert_eq!(cache.get(0), Some(1));
+        }
+    }
+
+    #[test]
+    fn tiered_cache_splits_by_value() {
+        let cache = TieredCache::new(100, 1000);
+        cache.insert(50, 25);
+        cache.insert(5_000, 30);
+        assert_eq!(cache.get(50), Some(25));
+        assert_eq!(cache.get(5_000), Some(30));
+        assert_eq!(cache.dense.stats().entries, 2); // 1 and 50
+        assert_eq!(cache.map.stats().entries, 1);
+        assert_eq!(cache.stats().hit_rate(), 1.0);
+
+        let mut entries = Vec::new();
+        cache.for_each_entry(&mut |n, len| entries.push((n, len)));
+        entries.sort();
+        assert_eq!(entries, [(1, 1), (50, 25), (5_000, 30)]);
+    }
+}
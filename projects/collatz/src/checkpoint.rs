@@ -0,0 +1,143 @@
+//! Checkpoints of a search in progress, so a search that takes hours can be
+//! stopped and picked up later where it left off.
+//!
+//! A checkpoint is a short header followed by the cache as a table:
+//!
+//! ```text
+//! magic      b"CLCP"
+//! version    u16
+//! input      u32, the path length being searched for
+//! start      u128, every start below it has been checked
+//! smallest   u128, the best start found so far, or 0 for none yet
+//! checksum   u64 FNV-1a of the header before it
+//! table      the cache, in the format table::save writes
+//! ```
+//!
+//! All numbers are little endian. The table has its own checksum and
+//! records the map the search was using.
+
+use crate::cache::LengthCache;
+use crate::map::CollatzMap;
+use crate::search::Progress;
+use crate::table::{self, TableError};
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"CLCP";
+
+/// Bumped whenever the layout changes.
+pub const VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 4 + 2 + 4 + 16 + 16;
+
+/// Write progress and the cache it was made with to out.
+pub fn save(
+    progress: &Progress,
+    cache: &dyn LengthCache,
+    map: &CollatzMap,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&progress.input.to_le_bytes());
+    header.extend_from_slice(&progress.start.to_le_bytes());
+    header.extend_from_slice(&progress.smallest.unwrap_or(0).to_le_bytes());
+
+    out.write_all(&header)?;
+    out.write_all(&table::checksum(&header).to_le_bytes())?;
+    table::save(cache, map, out)
+}
+
+/// Read a checkpoint written by save, filling cache from its table. As with
+/// table::load, a damaged checkpoint leaves the cache untouched.
+pub fn load(
+    cache: &dyn LengthCache,
+    map: &CollatzMap,
+    mut input: impl Read,
+) -> Result<Progress, TableError> {
+    let mut header = [0u8; HEADER_LEN + 8];
+    input
+        .read_exact(&mut header)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => TableError::NotATable,
+            _ => TableError::Io(err),
+        })?;
+
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(TableError::NotATable);
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != VERSION {
+        return Err(TableError::UnsupportedVersion(version));
+    }
+    let (header, checksum) = header.split_at(HEADER_LEN);
+    if table::checksum(header) != u64::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(TableError::BadChecksum);
+    }
+
+    let input_length = u32::from_le_bytes(header[6..10].try_into().unwrap());
+    let start = u128::from_le_bytes(header[10..26].try_into().unwrap());
+    let smallest = u128::from_le_bytes(header[26..42].try_into().unwrap());
+
+    table::load(cache, map, input)?;
+    Ok(Progress {
+        input: input_length,
+        start,
+        smallest: (smallest != 0).then_some(smallest),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::TieredCache;
+    use crate::search;
+
+    #[test]
+    fn round_trip_and_finish() {
+        let map = CollatzMap::STANDARD;
+        let cache = TieredCache::new(1 << 10, 1 << 10);
+        let mut progress = Progress::new(112);
+        search::advance(&map, &mut progress, 20, 1, &cache);
+        assert!(!progress.is_done(&map));
+
+        let mut file = Vec::new();
+        save(&progress, &cache, &map, &mut file).unwrap();
+
+        let loaded = TieredCache::new(1 << 10, 1 << 10);
+        let mut resumed = load(&loaded, &map, file.as_slice()).unwrap();
+        assert_eq!(resumed, progress);
+        assert_eq!(loaded.get(27), cache.get(27));
+
+        while !resumed.is_done(&map) {
+            search::advance(&map, &mut resumed, 20, 1, &loaded);
+        }
+        assert_eq!(resumed.answer(&map), Some(27u32.into()));
+    }
+
+    #[test]
+    fn rejects_bad_checkpoints() {
+        let map = CollatzMap::STANDARD;
+        let cache = TieredCache::new(1 << 10, 1 << 10);
+        let mut file = Vec::new();
+        save(&Progress::new(50), &cache, &map, &mut file).unwrap();
+        let empty = TieredCache::new(16, 16);
+
+        let mut damaged = file.clone();
+        damaged[12] ^= 1;
+        let err = load(&empty, &map, damaged.as_slice()).unwrap_err();
+        assert!(matches!(err, TableError::BadChecksum));
+
+        let err = load(&empty, &CollatzMap::FIVE_N_PLUS_ONE, file.as_slice()).unwrap_err();
+        assert!(matches!(err, TableError::WrongMap { .. }));
+
+        let err = load(&empty, &map, &file[..20]).unwrap_err();
+        assert!(matches!(err, TableError::NotATable));
+
+        // A table on its own is not a checkpoint
+        let mut table = Vec::new();
+        table::save(&cache, &map, &mut table).unwrap();
+        let err = load(&empty, &map, table.as_slice()).unwrap_err();
+        assert!(matches!(err, TableError::NotATable));
+    }
+}
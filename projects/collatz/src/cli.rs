@@ -0,0 +1,594 @@
+use collatz::cache::DEFAULT_CACHE_MB;
+use collatz::map::CollatzMap;
+use collatz::records::RangeReport;
+use collatz::tree::InverseTree;
+use collatz::BigUint;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// How often --checkpoint saves unless --checkpoint-every says otherwise.
+const DEFAULT_CHECKPOINT_EVERY: Duration = Duration::from_secs(60);
+
+pub const USAGE: &str = "\
+Usage: collatz [OPTIONS] [QUERY...]
+       collatz [OPTIONS] records --upto N
+       collatz [OPTIONS] tree --depth D
+
+Options: [--format plain|csv|json|dot] [--threads N] [--cache-mb MB] [--stats]
+         [--map an+b] [--divisor D] [--table FILE]
+         [--checkpoint FILE] [--checkpoint-every SECS] [--resume FILE]
+
+Each QUERY is a path length (50) or a range of them (1..=200 or 1..200).
+records lists the starts up to N whose paths are longer or climb higher
+than those of every smaller start, and how many starts have each length.
+tree prints the inverse tree of every start whose path reaches 1 within D
+steps, as an outline, value,parent,depth CSV, nested JSON or a Graphviz
+graph (--format dot).
+The search uses every CPU unless --threads says otherwise, and remembers
+path lengths in at most --cache-mb megabytes (default 256). --stats prints
+the cache hit rate to stderr after each query.
+--map and --divisor search a generalized map instead of 3n+1: numbers
+divisible by D (default 2) are divided by it and the rest go to an+b, as in
+--map 5n+1 or --map 3n-1.
+--table loads the cache from FILE at start (if it exists) and saves it back
+at the end, so later runs don't redo earlier work.
+--checkpoint saves how far the current search has got, and its cache, to
+FILE every --checkpoint-every seconds (default 60) and when Ctrl-C stops
+it. --resume FILE carries on from such a checkpoint, and with no queries
+answers the path length it was searching for.
+With no queries, path lengths are read one per line from stdin, either
+interactively or from a pipe.";
+
+/// How results are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Plain,
+    Csv,
+    Json,
+    /// Graphviz, for the tree command only.
+    Dot,
+}
+
+impl Format {
+    fn parse(text: &str) -> Result<Format, String> {
+        match text {
+            "plain" => Ok(Format::Plain),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "dot" => Ok(Format::Dot),
+            _ => Err(format!(
+                "Unknown format '{text}', expected plain, csv, json or dot"
+            )),
+        }
+    }
+}
+
+/// What the program was asked to do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Find the smallest start for each path length. No queries means read
+    /// them from stdin.
+    Search(Vec<RangeInclusive<u32>>),
+    /// Records and a histogram for every start up to a limit.
+    Records { upto: u128 },
+    /// The inverse tree down to a depth.
+    Tree { depth: u32 },
+}
+
+/// Parsed command line.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Options {
+    pub format: Format,
+    pub command: Command,
+    pub threads: usize,
+    pub cache_mb: usize,
+    pub stats: bool,
+    pub map: CollatzMap,
+    pub table: Option<PathBuf>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_every: Duration,
+    /// Start from the checkpoint file instead of just writing to it.
+    pub resume: bool,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        args.next(); // Throwaway program name
+
+        let mut options = Options {
+            format: Format::Plain,
+            command: Command::Search(Vec::new()),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            cache_mb: DEFAULT_CACHE_MB,
+            stats: false,
+            map: CollatzMap::STANDARD,
+            table: None,
+            checkpoint: None,
+            checkpoint_every: DEFAULT_CHECKPOINT_EVERY,
+            resume: false,
+            help: false,
+        };
+        let mut divisor = None;
+        let mut upto = None;
+        let mut depth = None;
+        let mut positional = Vec::new();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "-f" | "--format" => {
+                    let value = args.next().ok_or("--format needs a value")?;
+                    options.format = Format::parse(&value)?;
+                }
+                "-j" | "--threads" => options.threads = count(&arg, args.next())?,
+                "--cache-mb" => options.cache_mb = count(&arg, args.next())?,
+                "--stats" => options.stats = true,
+                "--map" => {
+                    let value = args.next().ok_or("--map needs a value")?;
+                    options.map = value.parse()?;
+                }
+                "--table" => {
+                    let value = args.next().ok_or("--table needs a file")?;
+                    options.table = Some(PathBuf::from(value));
+                }
+                "--checkpoint" | "--resume" => {
+                    let value = args.next().ok_or(format!("{arg} needs a file"))?;
+                    options.checkpoint = Some(PathBuf::from(value));
+                    options.resume |= arg == "--resume";
+                }
+                "--checkpoint-every" => {
+                    let secs = count(&arg, args.next())?;
+                    options.checkpoint_every = Duration::from_secs(secs as u64);
+                }
+                "--divisor" => divisor = Some(small_count(&arg, args.next())?),
+                "--depth" => depth = Some(small_count(&arg, args.next())?),
+                "--upto" => {
+                    let value = args.next().ok_or("--upto needs a value")?;
+                    upto = match value.parse::<u128>() {
+                        Ok(upto) if upto > 0 => Some(upto),
+                        _ => return Err(format!("--upto needs a positive number, got '{value}'")),
+                    };
+                }
+                _ => match arg.strip_prefix("--format=") {
+                    Some(value) => options.format = Format::parse(value)?,
+                    None => positional.push(arg),
+                },
+            }
+        }
+
+        options.command = match positional.first().map(String::as_str) {
+            Some("records") if positional.len() > 1 => {
+                return Err(String::from("records takes no queries, only --upto"))
+            }
+            Some("records") => Command::Records {
+                upto: upto.ok_or("records needs --upto N")?,
+            },
+            _ if upto.is_some() => return Err(String::from("--upto only goes with records")),
+            Some("tree") if positional.len() > 1 => {
+                return Err(String::from("tree takes no queries, only --depth"))
+            }
+            Some("tree") => Command::Tree {
+                depth: depth.ok_or("tree needs --depth D")?,
+            },
+            _ if depth.is_some() => return Err(String::from("--depth only goes with tree")),
+            _ => Command::Search(
+                positional
+                    .iter()
+                    .map(|arg| parse_query(arg))
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+
+        if options.format == Format::Dot && !matches!(options.command, Command::Tree { .. }) {
+            return Err(String::from("--format dot only goes with tree"));
+        }
+
+        if options.checkpoint.is_some() && !matches!(options.command, Command::Search(_)) {
+            return Err(String::from(
+                "--checkpoint and --resume only go with searches",
+            ));
+        }
+
+        if let Some(divisor) = divisor {
+            let map = options.map;
+            options.map = CollatzMap::new(map.multiplier(), map.increment(), divisor)?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// The positive number following an option like --threads.
+fn count(option: &str, value: Option<String>) -> Result<usize, String> {
+    let value = value.ok_or(format!("{option} needs a value"))?;
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("{option} needs a positive number, got '{value}'")),
+    }
+}
+
+/// The same, for options that have to fit in a u32.
+fn small_count(option: &str, value: Option<String>) -> Result<u32, String> {
+    let count = count(option, value)?;
+    u32::try_from(count).map_err(|_| format!("{option} can be at most {}, got {count}", u32::MAX))
+}
+
+/// Parse a single path length ("50") or a range of them ("1..=200", "1..200")
+/// into an inclusive range.
+pub fn parse_query(text: &str) -> Result<RangeInclusive<u32>, String> {
+    let text = text.trim();
+    let number = |part: &str| -> Result<u32, String> {
+        part.trim()
+            .parse()
+            .map_err(|_| format!("'{text}' is not a path length or range"))
+    };
+
+    if let Some((low, high)) = text.split_once("..=") {
+        Ok(number(low)?..=number(high)?)
+    } else if let Some((low, high)) = text.split_once("..") {
+        let high = number(high)?;
+        if high == 0 {
+            return Err(format!("'{text}' is an empty range"));
+        }
+        Ok(number(low)?..=high - 1)
+    } else {
+        let length = number(text)?;
+        Ok(length..=length)
+    }
+}
+
+/// Writes `length,smallest_start` rows in the chosen format. Rows are written
+/// as they come, so piped queries show up as soon as they are answered.
+pub struct Output<W: Write> {
+    out: W,
+    format: Format,
+    rows: usize,
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(out: W, format: Format) -> Output<W> {
+        Output {
+            out,
+            format,
+            rows: 0,
+        }
+    }
+
+    pub fn row(&mut self, length: u32, smallest: &BigUint) -> io::Result<()> {
+        match self.format {
+            Format::Plain | Format::Dot => writeln!(self.out, "{length}: {smallest}")?,
+            Format::Csv => {
+                if self.rows == 0 {
+                    writeln!(self.out, "length,smallest_start")?;
+                }
+                writeln!(self.out, "{length},{smallest}")?;
+            }
+            Format::Json => {
+                let open = if self.rows == 0 { "[" } else { "," };
+                writeln!(
+                    self.out,
+                    "{open}{{\"length\":{length},\"smallest_start\":{smallest}}}"
+                )?;
+            }
+        }
+        self.rows += 1;
+        self.out.flush()
+    }
+
+    /// Close off the document. Empty CSV still gets its header and empty JSON
+    /// is still an array.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.format {
+            Format::Plain | Format::Dot => (),
+            Format::Csv if self.rows == 0 => writeln!(self.out, "length,smallest_start")?,
+            Format::Csv => (),
+            Format::Json if self.rows == 0 => writeln!(self.out, "[]")?,
+            Format::Json => writeln!(self.out, "]")?,
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Write a records report as a table, as kind,key,value CSV rows, or as one
+/// JSON object.
+pub fn write_report(
+    mut out: impl Write,
+    format: Format,
+    upto: u128,
+    report: &RangeReport,
+) -> io::Result<()> {
+    let lengths: Vec<[String; 2]> = report
+        .length_records
+        .iter()
+        .map(|(start, len)| [start.to_string(), len.to_string()])
+        .collect();
+    let heights: Vec<[String; 2]> = report
+        .height_records
+        .iter()
+        .map(|(start, height)| [start.to_string(), height.to_string()])
+        .collect();
+    let histogram: Vec<[String; 2]> = report
+        .histogram
+        .iter()
+        .map(|(len, starts)| [len.to_string(), starts.to_string()])
+        .collect();
+
+    match format {
+        Format::Plain | Format::Dot => {
+            let title = format!("Path length records up to {upto}");
+            write_table(&mut out, &title, ["start", "length"], &lengths)?;
+            writeln!(out)?;
+            let title = format!("Height records up to {upto}");
+            write_table(&mut out, &title, ["start", "height"], &heights)?;
+            writeln!(out)?;
+            let title = format!("Path lengths of starts up to {upto}");
+            write_table(&mut out, &title, ["length", "starts"], &histogram)?;
+            if report.unresolved > 0 {
+                writeln!(out, "\n{} starts never reach 1", report.unresolved)?;
+            }
+        }
+        Format::Csv => {
+            writeln!(out, "kind,key,value")?;
+            let sections = [
+                ("length_record", &lengths),
+                ("height_record", &heights),
+                ("histogram", &histogram),
+            ];
+            for (kind, rows) in sections {
+                for [key, value] in rows {
+                    writeln!(out, "{kind},{key},{value}")?;
+                }
+            }
+            if report.unresolved > 0 {
+                writeln!(out, "unresolved,,{}", report.unresolved)?;
+            }
+        }
+        Format::Json => {
+            let list = |rows: &[[String; 2]], key: &str, value: &str| {
+                let items: Vec<String> = rows
+                    .iter()
+                    .map(|[k, v]| format!("{{\"{key}\":{k},\"{value}\":{v}}}"))
+                    .collect();
+                format!("[{}]", items.join(","))
+            };
+            writeln!(
+                out,
+                "{{\"upto\":{upto},\"length_records\":{},\"height_records\":{},\"histogram\":{},\"unresolved\":{}}}",
+                list(&lengths, "start", "length"),
+                list(&heights, "start", "height"),
+                list(&histogram, "length", "starts"),
+                report.unresolved
+            )?;
+        }
+    }
+    out.flush()
+}
+
+/// Write an inverse tree as an indented outline, value,parent,depth CSV rows,
+/// nested JSON or a Graphviz graph.
+pub fn write_tree(mut out: impl Write, format: Format, tree: &InverseTree) -> io::Result<()> {
+    let nodes = tree.nodes();
+    match format {
+        Format::Plain => {
+            let mut stack = vec![0];
+            while let Some(index) = stack.pop() {
+                let node = &nodes[index];
+                let indent = node.depth as usize * 2;
+                writeln!(out, "{:indent$}{}", "", node.value)?;
+                stack.extend(node.children.iter().rev());
+            }
+            out.flush()
+        }
+        Format::Csv => {
+            writeln!(out, "value,parent,depth")?;
+            for node in nodes {
+                let parent = node
+                    .parent
+                    .map_or(String::new(), |p| nodes[p].value.to_string());
+                writeln!(out, "{},{parent},{}", node.value, node.depth)?;
+            }
+            out.flush()
+        }
+        Format::Json => tree.write_json(out),
+        Format::Dot => tree.write_dot(out),
+    }
+}
+
+/// A title over two right-aligned columns.
+fn write_table(
+    out: &mut impl Write,
+    title: &str,
+    headers: [&str; 2],
+    rows: &[[String; 2]],
+) -> io::Result<()> {
+    let width = |column: usize| {
+        rows.iter()
+            .map(|row| row[column].len())
+            .chain([headers[column].len()])
+            .max()
+            .unwrap_or(0)
+    };
+    let (left, right) = (width(0), width(1));
+
+    writeln!(out, "{title}")?;
+    writeln!(out, "{:>left$}  {:>right$}", headers[0], headers[1])?;
+    for [key, value] in rows {
+        writeln!(out, "{key:>left$}  {value:>right$}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        let mut all = vec![String::from("collatz")];
+        all.extend(list.iter().map(|s| s.to_string()));
+        all.into_iter()
+    }
+
+    #[test]
+    fn parses_queries() {
+        assert_eq!(parse_query("50"), Ok(50..=50));
+        assert_eq!(parse_query(" 1..=200 "), Ok(1..=200));
+        assert_eq!(parse_query("1..200"), Ok(1..=199));
+        assert!(parse_query("fifty").is_err());
+        assert!(parse_query("1..").is_err());
+        assert!(parse_query("0..0").is_err());
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = Options::parse(args(&["--format", "csv", "-j", "2", "7", "1..=3"])).unwrap();
+        assert_eq!(options.format, Format::Csv);
+        assert_eq!(options.threads, 2);
+        assert_eq!(options.command, Command::Search(vec![7..=7, 1..=3]));
+
+        let options = Options::parse(args(&["--format=json"])).unwrap();
+        assert_eq!(options.format, Format::Json);
+        assert_eq!(options.command, Command::Search(Vec::new()));
+
+        assert!(Options::parse(args(&["--format", "xml"])).is_err());
+        assert!(Options::parse(args(&["--format"])).is_err());
+        assert!(Options::parse(args(&["--threads", "0"])).is_err());
+        assert!(Options::parse(args(&["--cache-mb", "lots"])).is_err());
+
+        let options = Options::parse(args(&["--cache-mb", "64", "--stats"])).unwrap();
+        assert_eq!(options.cache_mb, 64);
+        assert!(options.stats);
+        assert_eq!(options.map, CollatzMap::STANDARD);
+        assert_eq!(options.table, None);
+
+        let options = Options::parse(args(&[
+            "--table",
+            "t.bin",
+            "--map",
+            "5n-1",
+            "--divisor",
+            "3",
+        ]))
+        .unwrap();
+        assert_eq!(options.table, Some(PathBuf::from("t.bin")));
+        assert_eq!(options.map, CollatzMap::new(5, -1, 3).unwrap());
+        assert!(Options::parse(args(&["--divisor", "1"])).is_err());
+        assert_eq!(
+            Options::parse(args(&["--divisor", "4294967298"])).unwrap_err(),
+            "--divisor can be at most 4294967295, got 4294967298"
+        );
+        assert!(Options::parse(args(&["--map", "5m+1"])).is_err());
+    }
+
+    #[test]
+    fn parses_checkpoints() {
+        let options = Options::parse(args(&["--checkpoint", "cp.bin", "600"])).unwrap();
+        assert_eq!(options.checkpoint, Some(PathBuf::from("cp.bin")));
+        assert_eq!(options.checkpoint_every, DEFAULT_CHECKPOINT_EVERY);
+        assert!(!options.resume);
+
+        let options =
+            Options::parse(args(&["--resume", "cp.bin", "--checkpoint-every", "5"])).unwrap();
+        assert_eq!(options.checkpoint, Some(PathBuf::from("cp.bin")));
+        assert_eq!(options.checkpoint_every, Duration::from_secs(5));
+        assert!(options.resume);
+
+        assert!(Options::parse(args(&["--resume"])).is_err());
+        assert!(Options::parse(args(&["--checkpoint-every", "0"])).is_err());
+        assert!(Options::parse(args(&["records", "--upto", "9", "--checkpoint", "cp"])).is_err());
+    }
+
+    #[test]
+    fn parses_records() {
+        let options = Options::parse(args(&["records", "--upto", "1000"])).unwrap();
+        assert_eq!(options.command, Command::Records { upto: 1000 });
+
+        assert!(Options::parse(args(&["records"])).is_err());
+        assert!(Options::parse(args(&["records", "7", "--upto", "10"])).is_err());
+        assert!(Options::parse(args(&["7", "--upto", "10"])).is_err());
+    }
+
+    #[test]
+    fn parses_tree() {
+        let options = Options::parse(args(&["tree", "--depth", "12", "--format", "dot"])).unwrap();
+        assert_eq!(options.command, Command::Tree { depth: 12 });
+        assert_eq!(options.format, Format::Dot);
+
+        assert!(Options::parse(args(&["tree"])).is_err());
+        assert!(Options::parse(args(&["tree", "--depth", "0"])).is_err());
+        assert!(Options::parse(args(&["tree", "--depth", "4294967296"])).is_err());
+        assert!(Options::parse(args(&["7", "--depth", "3"])).is_err());
+        assert!(Options::parse(args(&["--format", "dot", "7"])).is_err());
+        assert!(Options::parse(args(&["records", "--upto", "9", "--format", "dot"])).is_err());
+    }
+
+    #[test]
+    fn writes_csv_and_json() {
+        let mut csv = Output::new(Vec::new(), Format::Csv);
+        csv.row(7, &BigUint::from(10u32)).unwrap();
+        csv.row(8, &BigUint::from(3u32)).unwrap();
+        let csv = String::from_utf8(csv.finish().unwrap()).unwrap();
+        assert_eq!(csv, "length,smallest_start\n7,10\n8,3\n");
+
+        let mut json = Output::new(Vec::new(), Format::Json);
+        json.row(7, &BigUint::from(10u32)).unwrap();
+        json.row(8, &BigUint::from(3u32)).unwrap();
+        let json = String::from_utf8(json.finish().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            "[{\"length\":7,\"smallest_start\":10}\n,{\"length\":8,\"smallest_start\":3}\n]\n"
+        );
+
+        let empty = Output::new(Vec::new(), Format::Json);
+        assert_eq!(empty.finish().unwrap(), b"[]\n");
+    }
+
+    #[test]
+    fn writes_reports() {
+        let report = RangeReport {
+            length_records: vec![(1, 1), (2, 2), (3, 8)],
+            height_records: vec![(1, BigUint::from(1u32)), (3, BigUint::from(16u32))],
+            histogram: [(1, 1), (8, 12)].into(),
+            unresolved: 0,
+        };
+
+        let mut table = Vec::new();
+        write_report(&mut table, Format::Plain, 3, &report).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert!(table.starts_with("Path length records up to 3\nstart  length\n    1       1\n"));
+        assert!(table.ends_with("length  starts\n     1       1\n     8      12\n"));
+
+        let mut csv = Vec::new();
+        write_report(&mut csv, Format::Csv, 3, &report).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.contains("\nheight_record,3,16\nhistogram,1,1\n"));
+
+        let mut json = Vec::new();
+        write_report(&mut json, Format::Json, 3, &report).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with("{\"upto\":3,\"length_records\":[{\"start\":1,\"length\":1},"));
+        assert!(json.ends_with("\"unresolved\":0}\n"));
+    }
+
+    #[test]
+    fn writes_trees() {
+        let tree = InverseTree::build(&CollatzMap::STANDARD, 5);
+
+        let mut outline = Vec::new();
+        write_tree(&mut outline, Format::Plain, &tree).unwrap();
+        assert_eq!(
+            String::from_utf8(outline).unwrap(),
+            "1\n  2\n    4\n      8\n        16\n          5\n          32\n"
+        );
+
+        let mut csv = Vec::new();
+        write_tree(&mut csv, Format::Csv, &tree).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("value,parent,depth\n1,,0\n2,1,1\n"));
+        assert!(csv.ends_with("5,16,5\n32,16,5\n"));
+    }
+}
@@ -0,0 +1,38 @@
+//! Ctrl-C handling for checkpointed searches. The SIGINT handler only sets a
+//! flag, which the search checks between pieces of work so it can save a
+//! checkpoint before exiting. A second Ctrl-C exits straight away.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C has been pressed since install().
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+pub fn install() {
+    const SIGINT: i32 = 2;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn _exit(status: i32) -> !;
+    }
+
+    // Only async-signal-safe work in here: an atomic swap and _exit
+    extern "C" fn on_interrupt(_: i32) {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            unsafe { _exit(130) }
+        }
+    }
+
+    unsafe {
+        signal(SIGINT, on_interrupt);
+    }
+}
+
+/// Elsewhere Ctrl-C keeps its usual meaning, and checkpoints are only saved
+/// on schedule.
+#[cfg(not(unix))]
+pub fn install() {}
@@ -0,0 +1,44 @@
+//! # Collatz
+//!
+//! `collatz` explores the Collatz map: halve even numbers, and send odd
+//! numbers n to 3n + 1. It can walk single paths, measure them, and search
+//! for the smallest start whose path has a given length.
+//!
+//! Path lengths count the values on a path, start and 1 included, so 1 has
+//! a path length of 1 and 10 (10, 5, 16, 8, 4, 2, 1) has a path length of 7.
+
+pub mod bigint;
+pub mod cache;
+pub mod checkpoint;
+pub mod map;
+pub mod records;
+pub mod search;
+pub mod sequence;
+pub mod table;
+pub mod tree;
+
+pub use self::bigint::BigUint;
+pub use self::sequence::{max_excursion, stopping_time, total_stopping_time, CollatzSequence};
+
+use self::cache::{TieredCache, DEFAULT_CACHE_MB};
+use std::thread;
+
+/// The smallest start whose path has the given length, 0 for a length of 0.
+///
+/// This searches on every CPU with a fresh cache of the default size. To pick
+/// the threads or reuse a cache between queries, call
+/// search::collatz_parallel directly.
+///
+/// # Examples
+///
+/// ```
+/// use collatz::BigUint;
+///
+/// // 10 -> 5 -> 16 -> 8 -> 4 -> 2 -> 1
+/// assert_eq!(collatz::first_with_path_length(7), BigUint::from(10u32));
+/// ```
+pub fn first_with_path_length(length: u32) -> BigUint {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let cache = TieredCache::with_megabytes(DEFAULT_CACHE_MB);
+    search::collatz_parallel(length, threads, &cache)
+}
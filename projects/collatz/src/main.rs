@@ -1,114 +1,319 @@
-use std::cmp::Ordering;
-use std::collections::{HashMap, VecDeque};
-/// Find the first collatz number with a path length of input.
-/// Print 0 if it doesnt exist.
-use std::io;
+mod cli;
+mod interrupt;
+
+use cli::{Command, Options, Output};
+use collatz::cache::{LengthCache, TieredCache};
+use collatz::search::Progress;
+use collatz::table::{self, TableError};
+use collatz::tree::InverseTree;
+use collatz::{checkpoint, records, search, BigUint};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, IsTerminal, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use std::{env, process};
+
+/// Starts each thread checks between looks at the Ctrl-C flag and the
+/// checkpoint clock.
+const CHUNK: u128 = 1 << 20;
 
 fn main() {
-    'get_input: loop {
-        let mut input = String::new();
+    let options = Options::parse(env::args()).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        eprintln!("{}", cli::USAGE);
+        process::exit(1);
+    });
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    // One cache for the whole run, so every query builds on the ones before
+    let cache = TieredCache::with_megabytes(options.cache_mb);
+    if let Some(path) = &options.table {
+        if let Err(err) = load_table(path, &cache, &options) {
+            eprintln!("Failed to load table {}: {err}", path.display());
+            process::exit(1);
+        }
+    }
+
+    let mut resumed = None;
+    if let Some(path) = &options.checkpoint {
+        if options.resume {
+            match load_checkpoint(path, &cache, &options) {
+                Ok(progress) => resumed = Some(progress),
+                Err(err) => {
+                    eprintln!("Failed to resume from {}: {err}", path.display());
+                    process::exit(1);
+                }
+            }
+        }
+        interrupt::install();
+    }
+
+    let mut session = Session {
+        output: Output::new(io::stdout(), options.format),
+        cache,
+        options,
+        resumed,
+    };
+
+    let command = session.options.command.clone();
+    let result = match &command {
+        // Resuming with no queries finishes the search that was checkpointed
+        Command::Search(queries) if queries.is_empty() && session.resumed.is_some() => {
+            let length = session.resumed.unwrap().input;
+            session.answer(length..=length).map(|_| true)
+        }
+        Command::Search(queries) if queries.is_empty() => session.read_queries(),
+        Command::Search(queries) => queries
+            .iter()
+            .try_for_each(|query| session.answer(query.clone()))
+            .map(|_| true),
+        Command::Records { upto } => session.records(*upto).map(|_| true),
+        Command::Tree { depth } => session.tree(*depth).map(|_| true),
+    };
 
-        println!("Enter path length you want to find, or 'q' to quit: ");
+    let Session {
+        output,
+        cache,
+        options,
+        resumed,
+    } = session;
 
-        io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line.");
+    // Reports are written whole, so only searches have a document to close
+    let result = match command {
+        Command::Search(_) => result.and_then(|ok| output.finish().map(|_| ok)),
+        Command::Records { .. } | Command::Tree { .. } => result,
+    };
 
-        // If no input, continue loop
-        if input.trim().len() == 0 {
-            continue;
+    // A closed pipe (e.g. piping into head) isn't worth complaining about
+    let mut code = match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => 0,
+        // The search already said where its checkpoint went
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => 130,
+        Err(err) => {
+            eprintln!("Failed to write output: {err}");
+            1
         }
+    };
 
-        // See if we quit
-        let q = String::from("q");
-        match input.trim().cmp(&q) {
-            Ordering::Equal => break 'get_input,
-            _ => (),
+    if let Some(progress) = resumed {
+        eprintln!(
+            "The checkpoint was searching for path length {}, which wasn't asked for",
+            progress.input
+        );
+    }
+
+    if let Some(path) = &options.table {
+        if let Err(err) = save_table(path, &cache, &options) {
+            eprintln!("Failed to save table {}: {err}", path.display());
+            code = code.max(1);
         }
+    }
 
-        // Parse input into u32
-        let input: u32 = input.trim().parse().expect("Please type a number!");
+    if code != 0 {
+        process::exit(code);
+    }
+}
 
-        println!("{}", collatz(input));
+/// Fill the cache from a table file saved by an earlier run, if there is one.
+fn load_table(path: &Path, cache: &TieredCache, options: &Options) -> Result<(), TableError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let count = table::load(cache, &options.map, BufReader::new(file))?;
+    if options.stats {
+        eprintln!("loaded {count} path lengths from {}", path.display());
     }
+    Ok(())
+}
+
+/// Save the cache for the next run.
+fn save_table(path: &Path, cache: &TieredCache, options: &Options) -> io::Result<()> {
+    replace_file(path, |out| table::save(cache, &options.map, out))
 }
 
-fn collatz(input: u32) -> u128 {
-    if input < 2 {
-        return input.into();
+/// Fill the cache from a checkpoint and say where its search got to.
+fn load_checkpoint(
+    path: &Path,
+    cache: &TieredCache,
+    options: &Options,
+) -> Result<Progress, TableError> {
+    let file = File::open(path)?;
+    let progress = checkpoint::load(cache, &options.map, BufReader::new(file))?;
+    if options.stats {
+        eprintln!(
+            "resuming the search for path length {} from start {}",
+            progress.input, progress.start
+        );
     }
+    Ok(progress)
+}
 
-    // Starting to find the result
-    // Variables
-    let mut start: u128 = 2; // start of the path
-    let mut length_found = false; // if we have found the length
-    let mut smallest: u128 = 2u128.saturating_pow(input); // the smallest the answer could be
-
-    // Hash map where start -> length
-    let mut start_length = HashMap::new();
-    start_length.insert(1u128, 1u32);
-
-    // Stores the path of the current working iteration
-    let mut d = VecDeque::new();
-    let mut len: u32 = 0; // length of the queue, since .len() -> usize
-
-    // While we havent found the answer
-    while start < smallest {
-        // if the start path length hasnt been found
-        if !start_length.contains_key(&start) {
-            d.clear();
-            d.push_back(start);
-            len = 1;
-            let mut next: u128 = if start % 2 == 0 {
-                start / 2
-            } else {
-                let (mul, of1) = start.overflowing_mul(3);
-                let (res, of2) = mul.overflowing_add(1);
-                if of1 || of2 {
-                    println!("Overflowing operation with start: {start}");
-                    return 0;
+/// Write a new version of a file next to the old one and then rename it
+/// over it, so a failed write never destroys the old file.
+fn replace_file(
+    path: &Path,
+    write: impl FnOnce(BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+
+    write(BufWriter::new(File::create(&temp)?))?;
+    fs::rename(&temp, path)
+}
+
+/// Everything the queries in one run share.
+struct Session<W: Write> {
+    options: Options,
+    cache: TieredCache,
+    output: Output<W>,
+    /// Where a checkpointed search got to, until its path length comes up.
+    resumed: Option<Progress>,
+}
+
+impl<W: Write> Session<W> {
+    /// Print the smallest start for every path length in the query.
+    fn answer(&mut self, query: RangeInclusive<u32>) -> io::Result<()> {
+        for length in query {
+            let smallest = self.search(length)?;
+            self.output.row(length, &smallest)?;
+        }
+        if self.options.stats {
+            eprintln!("cache: {}", self.cache.stats());
+        }
+        Ok(())
+    }
+
+    /// The smallest start with a path length of length. With --checkpoint
+    /// the search runs a chunk at a time, saving a checkpoint every so often
+    /// and returning an Interrupted error once Ctrl-C has been pressed.
+    fn search(&mut self, length: u32) -> io::Result<BigUint> {
+        let options = &self.options;
+        let Some(path) = &options.checkpoint else {
+            return Ok(search::collatz_parallel_with_map(
+                &options.map,
+                length,
+                options.threads,
+                &self.cache,
+            ));
+        };
+
+        let mut progress = self
+            .resumed
+            .take_if(|progress| progress.input == length)
+            .unwrap_or_else(|| Progress::new(length));
+        let mut saved = Instant::now();
+
+        while !progress.is_done(&options.map) {
+            let starts = CHUNK * options.threads as u128;
+            search::advance(
+                &options.map,
+                &mut progress,
+                starts,
+                options.threads,
+                &self.cache,
+            );
+
+            if interrupt::interrupted() {
+                if self.save_checkpoint(path, &progress) {
+                    eprintln!(
+                        "Interrupted at start {}, continue with --resume {}",
+                        progress.start,
+                        path.display()
+                    );
                 }
-                res
-            };
-
-            // while we dont know the path length of the next value
-            while !start_length.contains_key(&next) {
-                d.push_back(next);
-                len += 1;
-                next = if next % 2 == 0 {
-                    next / 2
-                } else {
-                    let (mul, of1) = next.overflowing_mul(3);
-                    let (res, of2) = mul.overflowing_add(1);
-                    if of1 || of2 {
-                        println!("Overflowing operation with start: {start}");
-                        return 0;
-                    }
-                    res
-                };
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            if saved.elapsed() >= options.checkpoint_every {
+                self.save_checkpoint(path, &progress);
+                saved = Instant::now();
             }
+        }
 
-            len = match start_length.get(&next) {
-                Some(value) => *value + len,
-                None => 0, // Shouldnt ever be the case since we broke above
-            };
-            for value in d.iter() {
-                if len == input {
-                    length_found = true;
-                    if *value < smallest {
-                        smallest = *value;
-                    }
-                }
-                start_length.insert(*value, len);
-                len -= 1;
+        Ok(progress.answer(&options.map).expect("the search is done"))
+    }
+
+    /// Save progress and the cache, reporting (but otherwise riding out)
+    /// any failure so a long search isn't lost to a full disk.
+    fn save_checkpoint(&self, path: &Path, progress: &Progress) -> bool {
+        let map = &self.options.map;
+        match replace_file(path, |out| {
+            checkpoint::save(progress, &self.cache, map, out)
+        }) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("Failed to save checkpoint {}: {err}", path.display());
+                false
             }
         }
-        start += 1;
     }
 
-    if !length_found {
-        return 0;
-    } else {
-        return smallest;
+    /// Print the records and histogram for every start up to upto.
+    fn records(&mut self, upto: u128) -> io::Result<()> {
+        let report = records::explore(&self.options.map, upto, &self.cache);
+        cli::write_report(io::stdout().lock(), self.options.format, upto, &report)?;
+        if self.options.stats {
+            eprintln!("cache: {}", self.cache.stats());
+        }
+        Ok(())
+    }
+
+    /// Print the inverse tree down to depth.
+    fn tree(&mut self, depth: u32) -> io::Result<()> {
+        let tree = InverseTree::build(&self.options.map, depth);
+        cli::write_tree(io::stdout().lock(), self.options.format, &tree)
+    }
+
+    /// Answer queries read one per line from stdin, prompting for them if stdin is
+    /// a terminal. Bad lines are reported and skipped. Returns false if there were
+    /// any.
+    fn read_queries(&mut self) -> io::Result<bool> {
+        let interactive = io::stdin().is_terminal();
+        let mut all_ok = true;
+        let mut line_number = 0;
+
+        loop {
+            let mut input = String::new();
+
+            if interactive {
+                eprintln!("Enter path length you want to find, or 'q' to quit: ");
+            }
+
+            // Stop at end of input
+            if io::stdin().read_line(&mut input)? == 0 {
+                break;
+            }
+            line_number += 1;
+
+            // If no input, continue loop
+            if input.trim().is_empty() {
+                continue;
+            }
+
+            // See if we quit
+            if input.trim() == "q" {
+                break;
+            }
+
+            match cli::parse_query(&input) {
+                Ok(query) => self.answer(query)?,
+                Err(err) if interactive => eprintln!("{err}. Please type a number!"),
+                Err(err) => {
+                    eprintln!("line {line_number}: {err}");
+                    all_ok = false;
+                }
+            }
+        }
+
+        Ok(all_ok)
     }
 }
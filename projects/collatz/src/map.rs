@@ -0,0 +1,367 @@
+use crate::bigint::BigUint;
+use crate::sequence::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A generalized Collatz map. Numbers divisible by `divisor` are divided by
+/// it, and every other number n goes to `multiplier * n + increment`.
+///
+/// The usual map is 3n+1 with a divisor of 2. Other choices, like 5n+1 or
+/// 3n-1, have paths that loop forever without reaching 1 or that seem to
+/// grow without bound, so anything walking them has to watch for both.
+///
+/// # Examples
+///
+/// ```
+/// use collatz::map::{CollatzMap, Orbit};
+/// use collatz::BigUint;
+///
+/// let cycle = [5u32, 14, 7, 20, 10].map(BigUint::from).to_vec();
+///
+/// assert_eq!(
+///     CollatzMap::THREE_N_MINUS_ONE.orbit(5u32, 1000),
+///     Orbit::Cycle { steps: 0, cycle },
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CollatzMap {
+    multiplier: u32,
+    increment: i32,
+    divisor: u32,
+}
+
+/// Where a path under a CollatzMap ends up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Orbit {
+    /// Reaches 1 after this many steps.
+    ReachesOne { steps: u32 },
+    /// Enters a loop that doesn't contain 1 after this many steps. The loop
+    /// is listed starting from its smallest value.
+    Cycle { steps: u32, cycle: Vec<BigUint> },
+    /// Reaches 0 or a negative number after this many steps, which can only
+    /// happen with a negative increment.
+    LeavesPositive { steps: u32 },
+    /// Still going after this many steps, which usually means it diverges.
+    Unresolved { steps: u32 },
+}
+
+impl CollatzMap {
+    /// 3n+1, the map of the Collatz conjecture.
+    pub const STANDARD: CollatzMap = CollatzMap {
+        multiplier: 3,
+        increment: 1,
+        divisor: 2,
+    };
+
+    /// 5n+1, which has cycles besides 1 and paths that appear to diverge.
+    pub const FIVE_N_PLUS_ONE: CollatzMap = CollatzMap {
+        multiplier: 5,
+        increment: 1,
+        divisor: 2,
+    };
+
+    /// 3n-1, the same as 3n+1 on negative numbers, with three known cycles.
+    pub const THREE_N_MINUS_ONE: CollatzMap = CollatzMap {
+        multiplier: 3,
+        increment: -1,
+        divisor: 2,
+    };
+
+    pub fn new(multiplier: u32, increment: i32, divisor: u32) -> Result<CollatzMap, String> {
+        if multiplier == 0 {
+            return Err(String::from("Multiplier must be at least 1."));
+        } else if divisor < 2 {
+            return Err(String::from("Divisor must be at least 2."));
+        }
+
+        Ok(CollatzMap {
+            multiplier,
+            increment,
+            divisor,
+        })
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        self.multiplier
+    }
+
+    pub fn increment(&self) -> i32 {
+        self.increment
+    }
+
+    pub fn divisor(&self) -> u32 {
+        self.divisor
+    }
+
+    /// The next value on the path, or None if it would be 0 or negative.
+    pub(crate) fn step(&self, value: Value) -> Option<Value> {
+        match value {
+            Value::Small(0) => None,
+            Value::Small(n) => {
+                // Plain shifts for the common case, since u128 division is slow
+                if self.divisor == 2 {
+                    if n & 1 == 0 {
+                        return Some(Value::Small(n >> 1));
+                    }
+                } else if n % self.divisor as u128 == 0 {
+                    return Some(Value::Small(n / self.divisor as u128));
+                }
+
+                let Some(product) = n.checked_mul(self.multiplier as u128) else {
+                    return Some(self.grow(BigUint::from(n)));
+                };
+                match product.checked_add_signed(self.increment as i128) {
+                    Some(0) => None,
+                    Some(next) => Some(Value::Small(next)),
+                    None if self.increment < 0 => None,
+                    None => Some(self.grow(BigUint::from(n))),
+                }
+            }
+            Value::Big(mut n) => {
+                if n.rem_small(self.divisor) == 0 {
+                    n.div_rem_small(self.divisor);
+                    Some(Value::from(n))
+                } else {
+                    Some(self.grow(n))
+                }
+            }
+        }
+    }
+
+    /// multiplier * n + increment, for n too big for u128 arithmetic. The
+    /// product is past u128::MAX, so the increment can't make it negative.
+    fn grow(&self, mut n: BigUint) -> Value {
+        n.mul_add_small(self.multiplier, self.increment.max(0) as u32);
+        if self.increment < 0 {
+            n.sub_small(self.increment.unsigned_abs());
+        }
+        Value::from(n)
+    }
+
+    /// The values that step to n, smallest first: divisor * n, and
+    /// (n - increment) / multiplier when that is a positive whole number not
+    /// divisible by divisor. Predecessors past u128::MAX are left out.
+    pub fn predecessors(&self, n: u128) -> Vec<u128> {
+        let mut found = Vec::with_capacity(2);
+
+        let multiplier = self.multiplier as u128;
+        let shifted = if self.increment < 0 {
+            n.checked_add(self.increment.unsigned_abs() as u128)
+        } else {
+            n.checked_sub(self.increment as u128)
+        };
+        if let Some(shifted) = shifted.filter(|m| m % multiplier == 0) {
+            let odd = shifted / multiplier;
+            if odd > 0 && !odd.is_multiple_of(self.divisor as u128) {
+                found.push(odd);
+            }
+        }
+        if let Some(even) = n.checked_mul(self.divisor as u128) {
+            found.push(even);
+        }
+
+        found.sort_unstable();
+        found
+    }
+
+    /// Follow the path from start for at most max_steps steps and say where
+    /// it ends up.
+    pub fn orbit(&self, start: impl Into<BigUint>, max_steps: u32) -> Orbit {
+        // Every value seen so far, with the step it was seen at
+        let mut seen = HashMap::new();
+        let mut current = Value::from(start.into());
+        if current == Value::Small(0) {
+            return Orbit::LeavesPositive { steps: 0 };
+        }
+
+        for steps in 0..=max_steps {
+            if current.is_one() {
+                return Orbit::ReachesOne { steps };
+            }
+            if let Some(first) = seen.insert(current.clone(), steps) {
+                return Orbit::Cycle {
+                    steps: first,
+                    cycle: self.cycle_from(current, steps - first),
+                };
+            }
+            if steps == max_steps {
+                break;
+            }
+            current = match self.step(current) {
+                Some(next) => next,
+                None => return Orbit::LeavesPositive { steps: steps + 1 },
+            };
+        }
+
+        Orbit::Unresolved { steps: max_steps }
+    }
+
+    /// The len values of the cycle through start, smallest first.
+    fn cycle_from(&self, start: Value, len: u32) -> Vec<BigUint> {
+        let mut cycle = Vec::new();
+        let mut current = start;
+        for _ in 0..len {
+            let next = self.step(current.clone()).expect("cycles stay positive");
+            cycle.push(current.into_big());
+            current = next;
+        }
+
+        let smallest = (0..cycle.len()).min_by(|&a, &b| cycle[a].cmp(&cycle[b]));
+        cycle.rotate_left(smallest.unwrap_or(0));
+        cycle
+    }
+}
+
+impl Default for CollatzMap {
+    fn default() -> CollatzMap {
+        CollatzMap::STANDARD
+    }
+}
+
+impl fmt::Display for CollatzMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}n{:+}", self.multiplier, self.increment)?;
+        if self.divisor != 2 {
+            write!(f, ", n/{}", self.divisor)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CollatzMap {
+    type Err = String;
+
+    /// Parse "an+b" or "an-b", like "5n+1", with a divisor of 2.
+    fn from_str(s: &str) -> Result<CollatzMap, String> {
+        let invalid = || format!("'{s}' is not a map like 3n+1 or 5n-1");
+
+        let (multiplier, increment) = s.trim().split_once('n').ok_or_else(invalid)?;
+        let multiplier = if multiplier.is_empty() {
+            1
+        } else {
+            multiplier.parse().map_err(|_| invalid())?
+        };
+        let increment = match increment.strip_prefix('+') {
+            _ if increment.is_empty() => 0,
+            Some(positive) if !positive.starts_with('-') => {
+                positive.parse().map_err(|_| invalid())?
+            }
+            Some(_) => return Err(invalid()),
+            None if increment.starts_with('-') => increment.parse().map_err(|_| invalid())?,
+            None => return Err(invalid()),
+        };
+
+        CollatzMap::new(multiplier, increment, 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(values: &[u32]) -> Vec<BigUint> {
+        values.iter().map(|&n| BigUint::from(n)).collect()
+    }
+
+    #[test]
+    fn parses_and_prints_maps() {
+        assert_eq!("3n+1".parse(), Ok(CollatzMap::STANDARD));
+        assert_eq!("5n+1".parse(), Ok(CollatzMap::FIVE_N_PLUS_ONE));
+        assert_eq!("3n-1".parse(), Ok(CollatzMap::THREE_N_MINUS_ONE));
+        assert_eq!("n+1".parse(), CollatzMap::new(1, 1, 2));
+        assert!("3x+1".parse::<CollatzMap>().is_err());
+        assert!("3n+-1".parse::<CollatzMap>().is_err());
+        assert!("0n+1".parse::<CollatzMap>().is_err());
+        assert!(CollatzMap::new(3, 1, 1).is_err());
+
+        assert_eq!(CollatzMap::THREE_N_MINUS_ONE.to_string(), "3n-1");
+        assert_eq!(CollatzMap::new(4, 1, 3).unwrap().to_string(), "4n+1, n/3");
+    }
+
+    #[test]
+    fn standard_orbits_reach_one() {
+        assert_eq!(
+            CollatzMap::STANDARD.orbit(27u32, 1000),
+            Orbit::ReachesOne { steps: 111 }
+        );
+        assert_eq!(
+            CollatzMap::STANDARD.orbit(27u32, 50),
+            Orbit::Unresolved { steps: 50 }
+        );
+    }
+
+    #[test]
+    fn finds_known_cycles() {
+        let five = CollatzMap::FIVE_N_PLUS_ONE;
+        assert_eq!(
+            five.orbit(13u32, 1000),
+            Orbit::Cycle {
+                steps: 0,
+                cycle: big(&[13, 66, 33, 166, 83, 416, 208, 104, 52, 26]),
+            }
+        );
+        // 17 is on the 17 -> 86 -> 43 -> ... cycle, while 172 halves into it
+        assert!(matches!(
+            five.orbit(17u32, 1000),
+            Orbit::Cycle { steps: 0, .. }
+        ));
+        assert!(matches!(
+            five.orbit(172u32, 1000),
+            Orbit::Cycle { steps: 1, .. }
+        ));
+
+        let minus = CollatzMap::THREE_N_MINUS_ONE;
+        assert_eq!(minus.orbit(3u32, 1000), Orbit::ReachesOne { steps: 4 });
+        match minus.orbit(17u32, 1000) {
+            Orbit::Cycle { steps: 0, cycle } => assert_eq!(cycle.len(), 18),
+            other => panic!("expected the 17 cycle, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn diverging_and_leaving_paths() {
+        // 7 under 5n+1 is believed to grow forever
+        assert_eq!(
+            CollatzMap::FIVE_N_PLUS_ONE.orbit(7u32, 500),
+            Orbit::Unresolved { steps: 500 }
+        );
+
+        let falls = CollatzMap::new(1, -4, 2).unwrap();
+        assert_eq!(falls.orbit(3u32, 10), Orbit::LeavesPositive { steps: 1 });
+        assert_eq!(falls.orbit(0u32, 10), Orbit::LeavesPositive { steps: 0 });
+    }
+
+    #[test]
+    fn predecessors_step_back() {
+        let standard = CollatzMap::STANDARD;
+        assert_eq!(standard.predecessors(16), [5, 32]);
+        assert_eq!(standard.predecessors(10), [3, 20]);
+        // (8 - 1) / 3 isn't whole, and (4 - 1) / 3 = 1 loops back round
+        assert_eq!(standard.predecessors(8), [16]);
+        assert_eq!(standard.predecessors(7), [14]); // 2 is even, so it halves instead
+        assert_eq!(standard.predecessors(4), [1, 8]);
+        assert_eq!(standard.predecessors(u128::MAX), []);
+
+        let minus = CollatzMap::THREE_N_MINUS_ONE;
+        assert_eq!(minus.predecessors(14), [5, 28]);
+
+        for map in [standard, minus, CollatzMap::new(4, 1, 3).unwrap()] {
+            for n in 1..200u128 {
+                for p in map.predecessors(n) {
+                    assert_eq!(map.step(Value::Small(p)), Some(Value::Small(n)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn steps_past_u128() {
+        let minus = CollatzMap::THREE_N_MINUS_ONE;
+        let next = minus.step(Value::Small(u128::MAX)).unwrap();
+        let mut expected = BigUint::from(u128::MAX);
+        expected.mul_add_small(3, 0);
+        expected.sub_small(1);
+        assert_eq!(next, Value::Big(expected));
+    }
+}
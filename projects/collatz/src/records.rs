@@ -0,0 +1,151 @@
+use crate::bigint::BigUint;
+use crate::cache::LengthCache;
+use crate::map::CollatzMap;
+use crate::search::{self, MIN_WALK_LIMIT};
+use crate::sequence::Value;
+use std::collections::BTreeMap;
+
+/// What explore() finds out about every start from 1 up to a limit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RangeReport {
+    /// (start, path length) for every start whose path is longer than the
+    /// path of every smaller start.
+    pub length_records: Vec<(u128, u32)>,
+    /// (start, highest value) for every start whose path climbs higher than
+    /// the path of every smaller start.
+    pub height_records: Vec<(u128, BigUint)>,
+    /// How many starts have each path length.
+    pub histogram: BTreeMap<u32, u64>,
+    /// Starts whose paths never reach 1, which only happens on other maps.
+    pub unresolved: u64,
+}
+
+/// Walk every start from 1 to upto and collect its records and histogram.
+///
+/// Path lengths come from (and go into) the cache, so this shares its work
+/// with searches that use the same cache.
+///
+/// # Examples
+///
+/// ```
+/// use collatz::cache::TieredCache;
+/// use collatz::map::CollatzMap;
+/// use collatz::records;
+///
+/// let cache = TieredCache::new(1 << 10, 1 << 10);
+/// let report = records::explore(&CollatzMap::STANDARD, 10, &cache);
+///
+/// assert_eq!(report.length_records, [(1, 1), (2, 2), (3, 8), (6, 9), (7, 17), (9, 20)]);
+/// ```
+pub fn explore(map: &CollatzMap, upto: u128, cache: &dyn LengthCache) -> RangeReport {
+    let mut report = RangeReport::default();
+    let mut longest = 0;
+    let mut highest = Value::Small(0);
+
+    for start in 1..=upto {
+        let Some(len) = search::path_length(map, start, cache) else {
+            report.unresolved += 1;
+            continue;
+        };
+
+        *report.histogram.entry(len).or_insert(0) += 1;
+        if len > longest {
+            longest = len;
+            report.length_records.push((start, len));
+        }
+        if let Some(height) = climb(map, start, &highest) {
+            report
+                .height_records
+                .push((start, height.clone().into_big()));
+            highest = height;
+        }
+    }
+
+    report
+}
+
+/// The highest value on the path from start, if it beats record.
+///
+/// Only the part of the path before it first drops below start needs
+/// walking. Everything after that is on the path of a smaller start, which
+/// by definition stays at or under the record.
+fn climb(map: &CollatzMap, start: u128, record: &Value) -> Option<Value> {
+    let start = Value::Small(start);
+    let mut current = start.clone();
+    let mut highest = start.clone();
+
+    for _ in 0..MIN_WALK_LIMIT {
+        // 1 loops back up to 4 under 3n+1, but its path ends at 1
+        if current.is_one() {
+            break;
+        }
+        current = map.step(current)?;
+        if current < start {
+            break;
+        }
+        if current > highest {
+            highest = current.clone();
+        }
+    }
+
+    (highest > *record).then_some(highest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::TieredCache;
+
+    fn explore_standard(upto: u128) -> RangeReport {
+        explore(
+            &CollatzMap::STANDARD,
+            upto,
+            &TieredCache::new(1 << 16, 1 << 16),
+        )
+    }
+
+    #[test]
+    fn length_records() {
+        let report = explore_standard(100);
+        let starts: Vec<u128> = report.length_records.iter().map(|r| r.0).collect();
+        assert_eq!(starts, [1, 2, 3, 6, 7, 9, 18, 25, 27, 54, 73, 97]);
+        assert_eq!(report.length_records.last(), Some(&(97, 119)));
+    }
+
+    #[test]
+    fn height_records() {
+        let report = explore_standard(100);
+        let records: Vec<(u128, String)> = report
+            .height_records
+            .iter()
+            .map(|(start, height)| (*start, height.to_string()))
+            .collect();
+        let expected = [
+            (1, "1"),
+            (2, "2"),
+            (3, "16"),
+            (7, "52"),
+            (15, "160"),
+            (27, "9232"),
+        ];
+        assert_eq!(records, expected.map(|(start, h)| (start, h.to_string())));
+    }
+
+    #[test]
+    fn histogram_counts_every_start() {
+        let report = explore_standard(1000);
+        assert_eq!(report.histogram.values().sum::<u64>(), 1000);
+        assert_eq!(report.histogram.get(&1), Some(&1)); // only 1 itself
+        assert_eq!(report.unresolved, 0);
+    }
+
+    #[test]
+    fn other_maps() {
+        let cache = TieredCache::new(1 << 16, 1 << 16);
+        let report = explore(&CollatzMap::FIVE_N_PLUS_ONE, 20, &cache);
+        // 5n+1 loses 5, 7, 9, 10, 11, 13, 14, 17, 18 and 20 to cycles or
+        // runaway growth
+        assert_eq!(report.unresolved, 10);
+        assert_eq!(report.histogram.values().sum::<u64>(), 10);
+    }
+}
@@ -0,0 +1,505 @@
+use crate::bigint::BigUint;
+use crate::cache::LengthCache;
+use crate::map::CollatzMap;
+use crate::sequence::Value;
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// How many starts a thread claims at a time in the parallel search.
+const BLOCK: u128 = 4096;
+
+/// Walks under 3n+1 are never abandoned before they reach this many values,
+/// and path_length gives up on other maps after this many.
+pub(crate) const MIN_WALK_LIMIT: u32 = 1000;
+
+/// Cached in place of a path length for values whose path never reaches 1.
+pub const NO_PATH: u32 = u32::MAX;
+
+/// Work out the path length of start and of every value on its path up to
+/// the first one the cache already knows. Each of those values is cached and
+/// passed to visit along with its length.
+///
+/// Paths that loop without reaching 1, or that leave the positive numbers,
+/// are cached as NO_PATH. A walk longer than limit values is abandoned without
+/// caching anything, since it may be diverging.
+fn walk(
+    map: &CollatzMap,
+    start: u128,
+    cache: &dyn LengthCache,
+    d: &mut VecDeque<Option<u128>>,
+    seen: &mut HashSet<u128>,
+    limit: usize,
+    mut visit: impl FnMut(u128, u32),
+) {
+    // 3n+1 has no cycles besides 1 (none exist below 2^68), so only other
+    // maps pay for keeping track of where the walk has been
+    let check_cycles = *map != CollatzMap::STANDARD;
+    seen.clear();
+    seen.insert(start);
+
+    d.clear();
+    d.push_back(Some(start));
+    let mut next = map.step(Value::Small(start));
+
+    // while we dont know the path length of the next value. The cache always
+    // knows 1, so on 3n+1 this ends.
+    let known = loop {
+        let Some(value) = next else {
+            break NO_PATH;
+        };
+        if let Some(n) = value.small() {
+            if let Some(len) = cache.get(n) {
+                break len;
+            }
+            if check_cycles && !seen.insert(n) {
+                break NO_PATH;
+            }
+        }
+        if d.len() >= limit {
+            return;
+        }
+        d.push_back(value.small());
+        next = map.step(value);
+    };
+
+    if known == NO_PATH {
+        for value in d.iter().flatten() {
+            cache.insert(*value, NO_PATH);
+        }
+        return;
+    }
+
+    let mut len = known + d.len() as u32;
+    for value in d.iter() {
+        if let Some(value) = *value {
+            visit(value, len);
+            cache.insert(value, len);
+        }
+        len -= 1;
+    }
+}
+
+/// How far a walk may go before it is abandoned. It is never less than the
+/// path length being searched for, since shorter walks couldn't find it. Paths
+/// under 3n+1 always end, so they get extra room to be cached in full, while
+/// other maps may have paths that grow forever and stop right there.
+fn walk_limit(map: &CollatzMap, input: u32) -> usize {
+    if *map == CollatzMap::STANDARD {
+        input.max(MIN_WALK_LIMIT) as usize
+    } else {
+        input as usize
+    }
+}
+
+/// The path length of n under map, from the cache if it is there and
+/// otherwise by walking (and caching) its path. None if the path never
+/// reaches 1. On maps other than 3n+1, paths still going after
+/// MIN_WALK_LIMIT values are given up on and also count as None.
+pub fn path_length(map: &CollatzMap, n: u128, cache: &dyn LengthCache) -> Option<u32> {
+    if n == 0 {
+        return None;
+    }
+
+    let len = match cache.get(n) {
+        Some(len) => len,
+        None => {
+            let limit = if *map == CollatzMap::STANDARD {
+                usize::MAX
+            } else {
+                MIN_WALK_LIMIT as usize
+            };
+            let mut found = NO_PATH;
+            let (mut d, mut seen) = (VecDeque::new(), HashSet::new());
+            walk(map, n, cache, &mut d, &mut seen, limit, |value, len| {
+                if value == n {
+                    found = len;
+                }
+            });
+            found
+        }
+    };
+    (len != NO_PATH).then_some(len)
+}
+
+/// Find the first collatz number with a path length of input.
+/// Return 0 for a path length of 0, which doesnt exist.
+pub fn collatz(input: u32, cache: &dyn LengthCache) -> BigUint {
+    collatz_with_map(&CollatzMap::STANDARD, input, cache)
+}
+
+/// Find the first number whose path under map has a length of input. The
+/// cache must only ever be used with this one map.
+pub fn collatz_with_map(map: &CollatzMap, input: u32, cache: &dyn LengthCache) -> BigUint {
+    if input < 2 {
+        return BigUint::from(input);
+    }
+
+    // divisor^(input - 1) divides straight down to 1, so it always has the
+    // path length we want. That makes it both the fallback answer and the
+    // point where the search can stop.
+    let bound = bound(map, input);
+
+    // Starting to find the result
+    // Variables
+    let mut start: u128 = 2; // start of the path
+    let mut length_found = false; // if we have found a start below bound
+    let mut smallest: u128 = bound.to_u128().unwrap_or(u128::MAX); // the smallest the answer could be
+
+    // Stores the path of the current working iteration. Values too big for
+    // a u128 are stored as None and are counted, but not cached.
+    let mut d = VecDeque::new();
+    let mut seen = HashSet::new();
+
+    // While we havent found the answer
+    while start < smallest {
+        match cache.get(start) {
+            // Starts go up one at a time, so a cached start is the answer
+            Some(len) if len == input => {
+                length_found = true;
+                smallest = start;
+            }
+            Some(_) => (),
+            None => walk(
+                map,
+                start,
+                cache,
+                &mut d,
+                &mut seen,
+                walk_limit(map, input),
+                |value, len| {
+                    if len == input && value < smallest {
+                        length_found = true;
+                        smallest = value;
+                    }
+                },
+            ),
+        }
+        start += 1;
+    }
+
+    if length_found {
+        BigUint::from(smallest)
+    } else {
+        bound
+    }
+}
+
+/// The same search as collatz(), split across threads.
+pub fn collatz_parallel(input: u32, threads: usize, cache: &dyn LengthCache) -> BigUint {
+    collatz_parallel_with_map(&CollatzMap::STANDARD, input, threads, cache)
+}
+
+/// The same search as collatz_with_map(), split across threads.
+///
+/// Threads claim blocks of starts in increasing order and share one cache.
+/// Every value whose path length gets worked out is a candidate, and the
+/// answer is the smallest candidate. A thread stops once its next block lies
+/// past the best candidate so far, and every block below the answer is always
+/// searched in full, so the result is the same as the sequential search no
+/// matter how the threads are scheduled.
+pub fn collatz_parallel_with_map(
+    map: &CollatzMap,
+    input: u32,
+    threads: usize,
+    cache: &dyn LengthCache,
+) -> BigUint {
+    if input < 2 || threads < 2 {
+        return collatz_with_map(map, input, cache);
+    }
+
+    let mut progress = Progress::new(input);
+    advance(map, &mut progress, u128::MAX, threads, cache);
+    progress
+        .answer(map)
+        .expect("a search with no limit on starts runs to the end")
+}
+
+/// How far a search for the smallest start with a path length of input has
+/// got. Searches can be run a piece at a time with advance(), and Progress
+/// is all that needs saving (along with the cache) to pick one up later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub input: u32,
+    /// Every start below this has been checked.
+    pub start: u128,
+    /// The smallest start found so far whose path has length input.
+    pub smallest: Option<u128>,
+}
+
+impl Progress {
+    /// A search that hasn't started yet.
+    pub fn new(input: u32) -> Progress {
+        Progress {
+            input,
+            start: 2,
+            smallest: None,
+        }
+    }
+
+    /// Whether every start that could beat smallest has been checked.
+    pub fn is_done(&self, map: &CollatzMap) -> bool {
+        self.input < 2 || self.start >= self.end(map)
+    }
+
+    /// The answer, once the search is done.
+    pub fn answer(&self, map: &CollatzMap) -> Option<BigUint> {
+        if self.input < 2 {
+            Some(BigUint::from(self.input))
+        } else if !self.is_done(map) {
+            None
+        } else {
+            Some(
+                self.smallest
+                    .map_or_else(|| bound(map, self.input), BigUint::from),
+            )
+        }
+    }
+
+    /// The first start the search never needs to look at.
+    fn end(&self, map: &CollatzMap) -> u128 {
+        self.smallest
+            .unwrap_or_else(|| bound(map, self.input).to_u128().unwrap_or(u128::MAX))
+    }
+}
+
+/// divisor^(input - 1), which divides straight down to 1 and so always has
+/// a path length of input.
+fn bound(map: &CollatzMap, input: u32) -> BigUint {
+    BigUint::pow_small(map.divisor(), input - 1)
+}
+
+/// Check up to the next starts starts of a search, on threads threads, and
+/// record what was found in progress.
+///
+/// # Examples
+///
+/// ```
+/// use collatz::cache::TieredCache;
+/// use collatz::map::CollatzMap;
+/// use collatz::search::{self, Progress};
+/// use collatz::BigUint;
+///
+/// let map = CollatzMap::STANDARD;
+/// let cache = TieredCache::new(1 << 10, 1 << 10);
+/// let mut progress = Progress::new(112);
+///
+/// // A few starts at a time, as if stopping to save in between
+/// while !progress.is_done(&map) {
+///     search::advance(&map, &mut progress, 10, 2, &cache);
+/// }
+/// assert_eq!(progress.answer(&map), Some(BigUint::from(27u32)));
+/// ```
+pub fn advance(
+    map: &CollatzMap,
+    progress: &mut Progress,
+    starts: u128,
+    threads: usize,
+    cache: &dyn LengthCache,
+) {
+    if progress.is_done(map) {
+        return;
+    }
+
+    let to = progress.start.saturating_add(starts).min(progress.end(map));
+    progress.smallest = search_range(map, progress, to, threads.max(1), cache);
+    // Every block below the new smallest was searched in full
+    progress.start = to.min(progress.end(map));
+}
+
+/// The parallel search over the starts from progress.start up to to.
+/// Returns the smallest start found so far, this time or before.
+fn search_range(
+    map: &CollatzMap,
+    progress: &Progress,
+    to: u128,
+    threads: usize,
+    cache: &dyn LengthCache,
+) -> Option<u128> {
+    let (input, from) = (progress.input, progress.start);
+    let limit = bound(map, input).to_u128().unwrap_or(u128::MAX);
+
+    let next_block = AtomicU64::new(0);
+    let best = Mutex::new(progress.smallest);
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                let mut d = VecDeque::new();
+                let mut seen = HashSet::new();
+                loop {
+                    let smallest = best.lock().unwrap().unwrap_or(limit);
+                    let block = next_block.fetch_add(1, Ordering::Relaxed) as u128;
+                    let low = from.saturating_add(block * BLOCK);
+                    if low >= smallest.min(to) {
+                        break;
+                    }
+
+                    let mut found: Option<u128> = None;
+                    let mut candidate = |value: u128, len: u32| {
+                        if len == input && value < smallest {
+                            found = Some(found.map_or(value, |f| f.min(value)));
+                        }
+                    };
+                    for start in low..low.saturating_add(BLOCK).min(smallest).min(to) {
+                        match cache.get(start) {
+                            Some(len) => candidate(start, len),
+                            None => walk(
+                                map,
+                                start,
+                                cache,
+                                &mut d,
+                                &mut seen,
+                                walk_limit(map, input),
+                                &mut candidate,
+                            ),
+                        }
+                    }
+
+                    if let Some(found) = found {
+                        let mut best = best.lock().unwrap();
+                        *best = Some(best.map_or(found, |b| b.min(found)));
+                    }
+                }
+            });
+        }
+    });
+
+    best.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::TieredCache;
+
+    fn find(input: u32) -> BigUint {
+        collatz(input, &TieredCache::new(1 << 16, 1 << 16))
+    }
+
+    #[test]
+    fn small_path_lengths() {
+        assert_eq!(find(0), BigUint::from(0u32));
+        assert_eq!(find(1), BigUint::from(1u32));
+        assert_eq!(find(2), BigUint::from(2u32));
+        assert_eq!(find(7), BigUint::from(10u32));
+        // 27 is famous for taking 111 steps, a path of 112 values
+        assert_eq!(find(112), BigUint::from(27u32));
+    }
+
+    #[test]
+    fn path_lengths_past_u128() {
+        // The search always finds something far smaller than 2^(n - 1), so
+        // the answer fits in a u128 even when the bound does not
+        let answer = find(130).to_u128().expect("answer fits in a u128");
+        assert!(answer < 1 << 20);
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        for input in (0..=140).step_by(7) {
+            let cache = TieredCache::new(1 << 16, 1 << 16);
+            assert_eq!(
+                collatz_parallel(input, 4, &cache),
+                find(input),
+                "path length {input}"
+            );
+        }
+        let cache = TieredCache::new(1 << 16, 1 << 16);
+        assert_eq!(collatz_parallel(112, 3, &cache), BigUint::from(27u32));
+    }
+
+    #[test]
+    fn searches_resume_in_pieces() {
+        for input in [7, 50, 112, 130] {
+            for threads in [1, 3] {
+                let mut progress = Progress::new(input);
+                while !progress.is_done(&CollatzMap::STANDARD) {
+                    // A fresh cache each time, like a restart that lost it
+                    let cache = TieredCache::new(1 << 10, 1 << 10);
+                    advance(&CollatzMap::STANDARD, &mut progress, 5_000, threads, &cache);
+                }
+                assert_eq!(
+                    progress.answer(&CollatzMap::STANDARD),
+                    Some(find(input)),
+                    "path length {input} on {threads} threads"
+                );
+            }
+        }
+
+        let mut progress = Progress::new(1);
+        assert!(progress.is_done(&CollatzMap::STANDARD));
+        advance(
+            &CollatzMap::STANDARD,
+            &mut progress,
+            10,
+            1,
+            &TieredCache::new(8, 8),
+        );
+        assert_eq!(
+            progress.answer(&CollatzMap::STANDARD),
+            Some(BigUint::from(1u32))
+        );
+
+        let progress = Progress::new(112);
+        assert_eq!(progress.answer(&CollatzMap::STANDARD), None);
+    }
+
+    #[test]
+    fn other_maps() {
+        let cache = TieredCache::new(1 << 16, 1 << 16);
+        let minus = CollatzMap::THREE_N_MINUS_ONE;
+        // 3 -> 8 -> 4 -> 2 -> 1, while 5 goes round a cycle and is skipped
+        assert_eq!(collatz_with_map(&minus, 5, &cache), BigUint::from(3u32));
+        assert_eq!(collatz_with_map(&minus, 6, &cache), BigUint::from(6u32));
+        assert_eq!(cache.get(5), Some(NO_PATH));
+
+        // 7 under 5n+1 seems to grow forever, which must not stall the search
+        let cache = TieredCache::new(1 << 16, 1 << 16);
+        let five = CollatzMap::FIVE_N_PLUS_ONE;
+        let answer = collatz_with_map(&five, 20, &cache);
+        assert_eq!(
+            crate::CollatzSequence::with_map(answer.clone(), five).count(),
+            20
+        );
+
+        let cache = TieredCache::new(1 << 16, 1 << 16);
+        assert_eq!(collatz_parallel_with_map(&five, 20, 3, &cache), answer);
+
+        // Dividing by 3 instead of 2: 3^4 = 81 is the bound for length 5
+        let thirds = CollatzMap::new(2, 1, 3).unwrap();
+        let cache = TieredCache::new(1 << 16, 1 << 16);
+        assert!(collatz_with_map(&thirds, 5, &cache) <= BigUint::from(81u32));
+    }
+
+    #[test]
+    fn path_lengths() {
+        let cache = TieredCache::new(1 << 10, 1 << 10);
+        let standard = CollatzMap::STANDARD;
+        assert_eq!(path_length(&standard, 0, &cache), None);
+        assert_eq!(path_length(&standard, 1, &cache), Some(1));
+        assert_eq!(path_length(&standard, 27, &cache), Some(112));
+        assert_eq!(cache.get(27), Some(112));
+
+        let cache = TieredCache::new(1 << 10, 1 << 10);
+        let five = CollatzMap::FIVE_N_PLUS_ONE;
+        assert_eq!(path_length(&five, 3, &cache), Some(6));
+        assert_eq!(path_length(&five, 13, &cache), None); // cycle
+        assert_eq!(path_length(&five, 7, &cache), None); // diverges
+    }
+
+    #[test]
+    fn small_and_reused_caches() {
+        // Constant eviction only costs time, not correctness
+        let tiny = TieredCache::new(16, 64);
+        assert_eq!(collatz(112, &tiny), BigUint::from(27u32));
+        assert!(tiny.stats().evictions > 0);
+
+        // A cache filled by one query answers the next one correctly
+        let cache = TieredCache::new(1 << 16, 1 << 16);
+        for input in 100..=120 {
+            assert_eq!(collatz(input, &cache), find(input), "path length {input}");
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::bigint::BigUint;
+use crate::map::CollatzMap;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// One value along a Collatz path. Almost every value fits in a u128 and can
+/// be cached; the rare ones that overflow are walked as a BigUint until the
+/// path comes back down.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Value {
+    Small(u128),
+    Big(BigUint),
+}
+
+impl Value {
+    /// The next value under 3n+1.
+    pub(crate) fn step(self) -> Value {
+        CollatzMap::STANDARD
+            .step(self)
+            .expect("3n+1 keeps positive numbers positive")
+    }
+
+    pub(crate) fn small(&self) -> Option<u128> {
+        match self {
+            Value::Small(n) => Some(*n),
+            Value::Big(_) => None,
+        }
+    }
+
+    pub(crate) fn is_one(&self) -> bool {
+        *self == Value::Small(1)
+    }
+
+    pub(crate) fn into_big(self) -> BigUint {
+        match self {
+            Value::Small(n) => BigUint::from(n),
+            Value::Big(n) => n,
+        }
+    }
+}
+
+impl From<BigUint> for Value {
+    fn from(n: BigUint) -> Value {
+        match n.to_u128() {
+            Some(small) => Value::Small(small),
+            None => Value::Big(n),
+        }
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Small(a), Value::Small(b)) => a.cmp(b),
+            // A Big never fits in a u128, so it is bigger than any Small
+            (Value::Small(_), Value::Big(_)) => Ordering::Less,
+            (Value::Big(_), Value::Small(_)) => Ordering::Greater,
+            (Value::Big(a), Value::Big(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The values on the Collatz path from a start down to 1, start and 1
+/// included.
+///
+/// # Examples
+///
+/// ```
+/// use collatz::CollatzSequence;
+///
+/// let path: Vec<String> = CollatzSequence::new(10u32).map(|n| n.to_string()).collect();
+///
+/// assert_eq!(path, ["10", "5", "16", "8", "4", "2", "1"]);
+/// ```
+///
+/// 0 never reaches 1 (it halves to itself), so its sequence is empty.
+///
+/// Under other maps (see with_map) the sequence also stops before the first
+/// repeated value and at the last value before the path leaves the positive
+/// numbers. A path that grows forever gives a sequence that never ends.
+pub struct CollatzSequence {
+    next: Option<Value>,
+    map: CollatzMap,
+    // Values so far, to spot cycles. 3n+1 has no cycles besides 1 (none exist
+    // below 2^68), so this stays None for it.
+    seen: Option<HashSet<Value>>,
+}
+
+impl CollatzSequence {
+    pub fn new(start: impl Into<BigUint>) -> CollatzSequence {
+        CollatzSequence::with_map(start, CollatzMap::STANDARD)
+    }
+
+    /// The path from start under a different map.
+    pub fn with_map(start: impl Into<BigUint>, map: CollatzMap) -> CollatzSequence {
+        let start = Value::from(start.into());
+        CollatzSequence {
+            next: (start != Value::Small(0)).then_some(start),
+            map,
+            seen: (map != CollatzMap::STANDARD).then(HashSet::new),
+        }
+    }
+}
+
+impl Iterator for CollatzSequence {
+    type Item = BigUint;
+
+    fn next(&mut self) -> Option<BigUint> {
+        let current = self.next.take()?;
+        if let Some(seen) = &mut self.seen {
+            if !seen.insert(current.clone()) {
+                return None;
+            }
+        }
+        if !current.is_one() {
+            self.next = self.map.step(current.clone());
+        }
+        Some(current.into_big())
+    }
+}
+
+/// Number of steps until the path first drops below its start, 0 for 0 and 1.
+///
+/// # Examples
+///
+/// ```
+/// // 3 -> 10 -> 5 -> 16 -> 8 -> 4 -> 2
+/// assert_eq!(collatz::stopping_time(3u32), 6);
+/// ```
+pub fn stopping_time(start: impl Into<BigUint>) -> u32 {
+    let start = Value::from(start.into());
+    if start <= Value::Small(1) {
+        return 0;
+    }
+
+    let mut steps = 0;
+    let mut current = start.clone();
+    while current >= start {
+        current = current.step();
+        steps += 1;
+    }
+    steps
+}
+
+/// Number of steps until the path reaches 1, 0 for 0 and 1. This is one less
+/// than the path length, which counts values rather than steps.
+///
+/// # Examples
+///
+/// ```
+/// // 27 famously takes 111 steps
+/// assert_eq!(collatz::total_stopping_time(27u32), 111);
+/// ```
+pub fn total_stopping_time(start: impl Into<BigUint>) -> u32 {
+    (CollatzSequence::new(start).count() as u32).saturating_sub(1)
+}
+
+/// The highest value on the path from start, which is start itself if the
+/// path only goes down.
+///
+/// # Examples
+///
+/// ```
+/// use collatz::BigUint;
+///
+/// assert_eq!(collatz::max_excursion(27u32), BigUint::from(9232u32));
+/// ```
+pub fn max_excursion(start: impl Into<BigUint>) -> BigUint {
+    let start = start.into();
+    CollatzSequence::new(start.clone()).max().unwrap_or(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_of_small_values() {
+        assert_eq!(CollatzSequence::new(0u32).count(), 0);
+        assert_eq!(
+            CollatzSequence::new(1u32).collect::<Vec<_>>(),
+            [BigUint::from(1u32)]
+        );
+        assert_eq!(CollatzSequence::new(27u32).count(), 112);
+    }
+
+    #[test]
+    fn sequence_through_big_values() {
+        // Starts above u128 still come back down to 1
+        let start = BigUint::pow2(130);
+        let mut big = start.clone();
+        big.mul_add_small(1, 1); // 2^130 + 1 climbs before it falls
+        assert_eq!(total_stopping_time(start), 130);
+        assert!(max_excursion(big.clone()) > big);
+        assert_eq!(CollatzSequence::new(big).last(), Some(BigUint::from(1u32)));
+    }
+
+    #[test]
+    fn steps_through_overflow() {
+        // An odd u128 near the top overflows on 3n+1 and comes back down
+        let mut value = Value::Small(u128::MAX).step();
+        assert!(value.small().is_none());
+        while value.small().is_none() {
+            value = value.step();
+        }
+        assert!(value.small().is_some());
+    }
+
+    #[test]
+    fn sequence_under_other_maps() {
+        // 5 -> 14 -> 7 -> 20 -> 10 -> 5 loops under 3n-1
+        let cycle = CollatzSequence::with_map(5u32, CollatzMap::THREE_N_MINUS_ONE);
+        assert_eq!(cycle.count(), 5);
+
+        let falls = CollatzMap::new(1, -4, 2).unwrap();
+        assert_eq!(CollatzSequence::with_map(3u32, falls).count(), 1);
+    }
+
+    #[test]
+    fn stopping_times() {
+        assert_eq!(stopping_time(0u32), 0);
+        assert_eq!(stopping_time(1u32), 0);
+        assert_eq!(stopping_time(2u32), 1);
+        assert_eq!(stopping_time(27u32), 96);
+        assert_eq!(total_stopping_time(1u32), 0);
+        assert_eq!(total_stopping_time(10u32), 6);
+        assert_eq!(max_excursion(1u32), BigUint::from(1u32));
+        assert_eq!(max_excursion(0u32), BigUint::from(0u32));
+    }
+}
@@ -0,0 +1,333 @@
+//! Saving a cache of path lengths to disk and loading it back, so long
+//! searches can pick up where an earlier session left off.
+//!
+//! The file is small and simple:
+//!
+//! ```text
+//! magic      b"CLTZ"
+//! version    u16, little endian
+//! map        multiplier u32, increment i32, divisor u32, little endian
+//! count      varint
+//! entries    count times: key delta (zigzag varint), path length (varint)
+//! checksum   u64 FNV-1a of everything before it, little endian
+//! ```
+//!
+//! Varints are LEB128: seven bits at a time, low bits first, with the top
+//! bit set on every byte but the last. Keys are stored as the difference
+//! from the previous key, so runs of consecutive values take one byte each.
+
+use crate::cache::LengthCache;
+use crate::map::CollatzMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"CLTZ";
+
+/// Bumped whenever the layout changes. Files with any other version are
+/// rejected rather than misread.
+pub const VERSION: u16 = 1;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Everything that can go wrong loading a table.
+#[derive(Debug)]
+pub enum TableError {
+    Io(io::Error),
+    NotATable,
+    UnsupportedVersion(u16),
+    WrongMap { expected: CollatzMap, found: String },
+    BadChecksum,
+    Corrupt,
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Io(err) => write!(f, "{err}"),
+            TableError::NotATable => write!(f, "not a collatz table file"),
+            TableError::UnsupportedVersion(version) => write!(
+                f,
+                "table format version {version} is not supported (expected {VERSION})"
+            ),
+            TableError::WrongMap { expected, found } => {
+                write!(f, "table was built for {found}, not {expected}")
+            }
+            TableError::BadChecksum => write!(f, "table checksum does not match, file is damaged"),
+            TableError::Corrupt => write!(f, "table is truncated or damaged"),
+        }
+    }
+}
+
+impl Error for TableError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TableError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TableError {
+    fn from(err: io::Error) -> TableError {
+        TableError::Io(err)
+    }
+}
+
+/// Write every entry in cache, which was filled using map, to out.
+pub fn save(cache: &dyn LengthCache, map: &CollatzMap, out: impl Write) -> io::Result<()> {
+    let mut out = Checksummed::new(out);
+
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&map.multiplier().to_le_bytes())?;
+    out.write_all(&map.increment().to_le_bytes())?;
+    out.write_all(&map.divisor().to_le_bytes())?;
+
+    let mut count: u128 = 0;
+    cache.for_each_entry(&mut |_, _| count += 1);
+    write_varint(&mut out, count)?;
+
+    // The cache can't pass errors out of for_each_entry, so hold on to the
+    // first one and skip the remaining writes
+    let mut result = Ok(());
+    let mut previous = 0u128;
+    cache.for_each_entry(&mut |n, len| {
+        if result.is_ok() {
+            let delta = n.wrapping_sub(previous) as i128;
+            previous = n;
+            result = write_varint(&mut out, zigzag(delta))
+                .and_then(|_| write_varint(&mut out, len as u128));
+        }
+    });
+    result?;
+
+    let checksum = out.hash;
+    out.inner.write_all(&checksum.to_le_bytes())?;
+    out.inner.flush()
+}
+
+/// Read a table written by save into cache. The whole table is checked
+/// before anything is added, so a damaged file leaves the cache untouched.
+pub fn load(
+    cache: &dyn LengthCache,
+    map: &CollatzMap,
+    mut input: impl Read,
+) -> Result<u64, TableError> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+
+    if bytes.len() < MAGIC.len() + 2 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(TableError::NotATable);
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(TableError::UnsupportedVersion(version));
+    }
+
+    let (body, checksum) = bytes.split_at(bytes.len().checked_sub(8).ok_or(TableError::Corrupt)?);
+    if fnv1a(FNV_OFFSET, body) != u64::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(TableError::BadChecksum);
+    }
+
+    let mut reader = &body[6..];
+    let multiplier = u32::from_le_bytes(take(&mut reader)?);
+    let increment = i32::from_le_bytes(take(&mut reader)?);
+    let divisor = u32::from_le_bytes(take(&mut reader)?);
+    let found = CollatzMap::new(multiplier, increment, divisor).map_err(|_| TableError::Corrupt)?;
+    if found != *map {
+        return Err(TableError::WrongMap {
+            expected: *map,
+            found: found.to_string(),
+        });
+    }
+
+    // Decode everything first so nothing is cached from a bad table
+    let count = read_varint(&mut reader)?;
+    let mut entries = Vec::with_capacity(count.min(reader.len() as u128) as usize);
+    let mut previous = 0u128;
+    for _ in 0..count {
+        let n = previous.wrapping_add(unzigzag(read_varint(&mut reader)?) as u128);
+        let len = u32::try_from(read_varint(&mut reader)?).map_err(|_| TableError::Corrupt)?;
+        entries.push((n, len));
+        previous = n;
+    }
+    if !reader.is_empty() {
+        return Err(TableError::Corrupt);
+    }
+
+    for (n, len) in &entries {
+        cache.insert(*n, *len);
+    }
+    Ok(entries.len() as u64)
+}
+
+/// A writer that keeps an FNV-1a hash of everything written through it.
+struct Checksummed<W: Write> {
+    inner: W,
+    hash: u64,
+}
+
+impl<W: Write> Checksummed<W> {
+    fn new(inner: W) -> Checksummed<W> {
+        Checksummed {
+            inner,
+            hash: FNV_OFFSET,
+        }
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hash = fnv1a(self.hash, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The FNV-1a hash of bytes, as used for table checksums.
+pub(crate) fn checksum(bytes: &[u8]) -> u64 {
+    fnv1a(FNV_OFFSET, bytes)
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Map signed to unsigned so small negative numbers stay small:
+/// 0, -1, 1, -2, 2 ... become 0, 1, 2, 3, 4 ...
+fn zigzag(n: i128) -> u128 {
+    ((n << 1) ^ (n >> 127)) as u128
+}
+
+fn unzigzag(n: u128) -> i128 {
+    (n >> 1) as i128 ^ -((n & 1) as i128)
+}
+
+fn write_varint(out: &mut impl Write, mut n: u128) -> io::Result<()> {
+    let mut buf = [0u8; 19]; // ceil(128 / 7)
+    let mut len = 0;
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    out.write_all(&buf[..len])
+}
+
+fn read_varint(reader: &mut &[u8]) -> Result<u128, TableError> {
+    let mut n = 0u128;
+    for shift in (0..128).step_by(7) {
+        let (&byte, rest) = reader.split_first().ok_or(TableError::Corrupt)?;
+        *reader = rest;
+        n |= ((byte & 0x7f) as u128)
+            .checked_shl(shift)
+            .filter(|part| part >> shift == (byte & 0x7f) as u128)
+            .ok_or(TableError::Corrupt)?;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(TableError::Corrupt)
+}
+
+fn take<const N: usize>(reader: &mut &[u8]) -> Result<[u8; N], TableError> {
+    if reader.len() < N {
+        return Err(TableError::Corrupt);
+    }
+    let (bytes, rest) = reader.split_at(N);
+    *reader = rest;
+    Ok(bytes.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::TieredCache;
+    use crate::search::NO_PATH;
+
+    fn filled() -> TieredCache {
+        let cache = TieredCache::new(100, 1000);
+        for n in 2..100 {
+            cache.insert(n, n as u32 + 1);
+        }
+        cache.insert(5_000, 30);
+        cache.insert(u128::MAX, NO_PATH);
+        cache
+    }
+
+    fn entries(cache: &dyn LengthCache) -> Vec<(u128, u32)> {
+        let mut entries = Vec::new();
+        cache.for_each_entry(&mut |n, len| entries.push((n, len)));
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn round_trip() {
+        let cache = filled();
+        let mut file = Vec::new();
+        save(&cache, &CollatzMap::STANDARD, &mut file).unwrap();
+        // Consecutive keys cost a byte each, so this stays small
+        assert!(file.len() < 300, "table took {} bytes", file.len());
+
+        let loaded = TieredCache::new(100, 1000);
+        let count = load(&loaded, &CollatzMap::STANDARD, file.as_slice()).unwrap();
+        assert_eq!(count, 101);
+        assert_eq!(entries(&loaded), entries(&cache));
+    }
+
+    #[test]
+    fn varints() {
+        for n in [0u128, 1, 127, 128, 300, u64::MAX as u128, u128::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, n).unwrap();
+            assert_eq!(read_varint(&mut buf.as_slice()).unwrap(), n);
+        }
+        for n in [0i128, -1, 1, i128::MIN, i128::MAX] {
+            assert_eq!(unzigzag(zigzag(n)), n);
+        }
+        assert!(read_varint(&mut [0x80u8].as_slice()).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let mut file = Vec::new();
+        save(&filled(), &CollatzMap::STANDARD, &mut file).unwrap();
+        let empty = TieredCache::new(100, 1000);
+
+        let err = load(&empty, &CollatzMap::FIVE_N_PLUS_ONE, file.as_slice()).unwrap_err();
+        assert!(matches!(err, TableError::WrongMap { .. }));
+
+        let mut damaged = file.clone();
+        damaged[20] ^= 1;
+        let err = load(&empty, &CollatzMap::STANDARD, damaged.as_slice()).unwrap_err();
+        assert!(matches!(err, TableError::BadChecksum));
+
+        let mut newer = file.clone();
+        newer[4] = 2;
+        let err = load(&empty, &CollatzMap::STANDARD, newer.as_slice()).unwrap_err();
+        assert!(matches!(err, TableError::UnsupportedVersion(2)));
+
+        let err = load(&empty, &CollatzMap::STANDARD, &b"hello"[..]).unwrap_err();
+        assert!(matches!(err, TableError::NotATable));
+
+        // Nothing was loaded along the way
+        assert_eq!(entries(&empty), [(1, 1)]);
+    }
+}
@@ -0,0 +1,237 @@
+//! The inverse Collatz tree: 1 at the root, and below every value the values
+//! that step to it. Depth d holds exactly the starts whose paths have length
+//! d + 1, so the smallest value at depth d is what search::collatz(d + 1)
+//! looks for.
+//!
+//! The tree roughly multiplies by 4/3 at every level, so depths much past 40
+//! make very large trees.
+
+use crate::map::CollatzMap;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+/// One value in an InverseTree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub value: u128,
+    /// Steps from value down to 1.
+    pub depth: u32,
+    /// Index of the node value steps to, None for the root.
+    pub parent: Option<usize>,
+    /// Indexes of the nodes that step to value, smallest value first.
+    pub children: Vec<usize>,
+}
+
+/// The inverse tree of a map, built breadth first from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InverseTree {
+    map: CollatzMap,
+    depth: u32,
+    nodes: Vec<Node>,
+}
+
+impl InverseTree {
+    /// Every value whose path reaches 1 in at most depth steps.
+    ///
+    /// Each value appears once. Under 3n+1, 1 steps to 4, so it is also a
+    /// predecessor of 4; that edge back to the root is left out. Values past
+    /// u128::MAX are left out too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use collatz::map::CollatzMap;
+    /// use collatz::tree::InverseTree;
+    ///
+    /// let tree = InverseTree::build(&CollatzMap::STANDARD, 6);
+    ///
+    /// // 10 -> 5 -> 16 -> 8 -> 4 -> 2 -> 1 and 64 -> 32 -> ... -> 1
+    /// assert_eq!(tree.level(6), [10, 64]);
+    /// ```
+    pub fn build(map: &CollatzMap, depth: u32) -> InverseTree {
+        let mut nodes = vec![Node {
+            value: 1,
+            depth: 0,
+            parent: None,
+            children: Vec::new(),
+        }];
+        let mut seen = HashSet::from([1]);
+
+        // Nodes are appended level by level, so the parents of the next
+        // level are always nodes[level_start..]
+        let mut level_start = 0;
+        for level in 1..=depth {
+            let level_end = nodes.len();
+            for parent in level_start..level_end {
+                for value in map.predecessors(nodes[parent].value) {
+                    if seen.insert(value) {
+                        let index = nodes.len();
+                        nodes[parent].children.push(index);
+                        nodes.push(Node {
+                            value,
+                            depth: level,
+                            parent: Some(parent),
+                            children: Vec::new(),
+                        });
+                    }
+                }
+            }
+            level_start = level_end;
+        }
+
+        InverseTree {
+            map: *map,
+            depth,
+            nodes,
+        }
+    }
+
+    pub fn map(&self) -> &CollatzMap {
+        &self.map
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Every node, level by level. The root is nodes()[0].
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// The values at one depth, smallest first.
+    pub fn level(&self, depth: u32) -> Vec<u128> {
+        let mut values: Vec<u128> = self
+            .nodes
+            .iter()
+            .filter(|node| node.depth == depth)
+            .map(|node| node.value)
+            .collect();
+        values.sort_unstable();
+        values
+    }
+
+    /// Write the tree as a Graphviz digraph with 1 at the top and an edge
+    /// from every value to the value it steps to. Steps that divide are solid
+    /// and steps through an+b are dashed.
+    pub fn write_dot(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "digraph collatz {{")?;
+        writeln!(
+            out,
+            "  label=\"Inverse {} tree to depth {}\";",
+            self.map, self.depth
+        )?;
+        writeln!(out, "  rankdir=BT;")?;
+        writeln!(out, "  node [shape=circle];")?;
+        writeln!(out, "  1;")?;
+        for node in &self.nodes[1..] {
+            let parent = self.nodes[node.parent.expect("only the root has no parent")].value;
+            let style = if node.value % self.map.divisor() as u128 == 0 {
+                ""
+            } else {
+                " [style=dashed]"
+            };
+            writeln!(out, "  {} -> {parent}{style};", node.value)?;
+        }
+        writeln!(out, "}}")?;
+        out.flush()
+    }
+
+    /// Write the tree as nested JSON objects, each with its value, depth and
+    /// children.
+    pub fn write_json(&self, mut out: impl Write) -> io::Result<()> {
+        write!(
+            out,
+            "{{\"map\":\"{}\",\"depth\":{},\"root\":",
+            self.map, self.depth
+        )?;
+        self.write_json_node(&mut out, 0)?;
+        writeln!(out, "}}")?;
+        out.flush()
+    }
+
+    fn write_json_node(&self, out: &mut impl Write, index: usize) -> io::Result<()> {
+        let node = &self.nodes[index];
+        write!(
+            out,
+            "{{\"value\":{},\"depth\":{},\"children\":[",
+            node.value, node.depth
+        )?;
+        for (i, child) in node.children.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            self.write_json_node(out, *child)?;
+        }
+        write!(out, "]}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::TieredCache;
+    use crate::search;
+    use crate::BigUint;
+
+    #[test]
+    fn levels_match_path_lengths() {
+        let tree = InverseTree::build(&CollatzMap::STANDARD, 8);
+        assert_eq!(tree.level(0), [1]);
+        assert_eq!(tree.level(4), [16]);
+        assert_eq!(tree.level(5), [5, 32]);
+        assert_eq!(tree.level(7), [3, 20, 21, 128]);
+        assert_eq!(tree.nodes().len(), 1 + 1 + 1 + 1 + 1 + 2 + 2 + 4 + 4);
+    }
+
+    #[test]
+    fn smallest_at_each_depth_is_the_search_answer() {
+        let tree = InverseTree::build(&CollatzMap::STANDARD, 30);
+        let cache = TieredCache::new(1 << 16, 1 << 16);
+        for depth in 0..=30 {
+            let smallest = tree.level(depth)[0];
+            assert_eq!(
+                search::collatz(depth + 1, &cache),
+                BigUint::from(smallest),
+                "depth {depth}"
+            );
+        }
+    }
+
+    #[test]
+    fn other_maps_skip_their_cycles() {
+        // 3n-1 has 1 -> 2 -> 1, so 1 must not show up again under 2
+        let tree = InverseTree::build(&CollatzMap::THREE_N_MINUS_ONE, 4);
+        assert_eq!(tree.level(1), [2]);
+        assert_eq!(tree.level(2), [4]);
+        assert_eq!(tree.level(3), [8]);
+        assert_eq!(tree.level(4), [3, 16]);
+    }
+
+    #[test]
+    fn writes_dot_and_json() {
+        let tree = InverseTree::build(&CollatzMap::STANDARD, 5);
+
+        let mut dot = Vec::new();
+        tree.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph collatz {\n  label=\"Inverse 3n+1 tree to depth 5\";\n"));
+        assert!(dot.contains("\n  5 -> 16 [style=dashed];\n  32 -> 16;\n"));
+        assert!(dot.ends_with("}\n"));
+
+        let mut json = Vec::new();
+        tree.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert_eq!(
+            json,
+            "{\"map\":\"3n+1\",\"depth\":5,\"root\":\
+             {\"value\":1,\"depth\":0,\"children\":[\
+             {\"value\":2,\"depth\":1,\"children\":[\
+             {\"value\":4,\"depth\":2,\"children\":[\
+             {\"value\":8,\"depth\":3,\"children\":[\
+             {\"value\":16,\"depth\":4,\"children\":[\
+             {\"value\":5,\"depth\":5,\"children\":[]},\
+             {\"value\":32,\"depth\":5,\"children\":[]}]}]}]}]}]}}\n"
+        );
+    }
+}
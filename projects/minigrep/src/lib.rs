@@ -1,29 +1,85 @@
 use std::error::Error;
-use std::{fs, env};
+use std::{fmt, fs, env, io};
+
+// Everything that can go wrong in minigrep. Argument problems come out of
+// Config::build, and file problems come out of run.
+#[derive(Debug)]
+pub enum MinigrepError {
+	MissingQuery,
+	MissingFilePath,
+	UnknownOption(String),
+	Io { path: String, source: io::Error },
+}
+
+impl fmt::Display for MinigrepError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MinigrepError::MissingQuery => write!(f, "Didn't get a query string"),
+			MinigrepError::MissingFilePath => write!(f, "Didn't get a file path"),
+			MinigrepError::UnknownOption(opt) => write!(f, "Unknown option: {opt}"),
+			MinigrepError::Io { path, source } => write!(f, "{path}: {source}"),
+		}
+	}
+}
+
+impl Error for MinigrepError {
+	// Only I/O errors wrap an underlying error
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			MinigrepError::Io { source, .. } => Some(source),
+			_ => None,
+		}
+	}
+}
+
+// grep-compatible exit codes: 0 if a line matched, 1 if nothing matched, and
+// 2 if an error happened (even if some other file matched).
+pub const EXIT_MATCH: i32 = 0;
+pub const EXIT_NO_MATCH: i32 = 1;
+pub const EXIT_ERROR: i32 = 2;
 
 pub struct Config {
 	pub query: String,
-	pub file_path: String,
+	pub file_paths: Vec<String>,
 	pub ignore_case: bool,
+	pub no_messages: bool,
 }
 
 impl Config {
-	// Returning a Config struct in Ok, or a static string wrapped around Err
+	// Returning a Config struct in Ok, or a MinigrepError wrapped around Err.
+	// Options come before the query, and "--" ends the options so a query can
+	// start with a dash.
+	// EXAMPLE: $  cargo run -- -s to poem.txt missing.txt
 	pub fn build(
 		mut args: impl Iterator<Item = String>,
-	) -> Result<Config, &'static str> {
+	) -> Result<Config, MinigrepError> {
 		args.next(); // Throwaway filename
 		
-		let query = match args.next() {
-			Some(arg) => arg,
-			None => return Err("Didn't get a query string"),
-		};
+		let mut no_messages = false;
 
-		let	file_path = match args.next() {
-			Some(arg) => arg,
-			None => return Err("Didn't get a file path"),
+		let query = loop {
+			match args.next() {
+				Some(arg) if arg == "--" => match args.next() {
+					Some(arg) => break arg,
+					None => return Err(MinigrepError::MissingQuery),
+				},
+				Some(arg) if arg == "-s" || arg == "--no-messages" => {
+					no_messages = true;
+				}
+				Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+					return Err(MinigrepError::UnknownOption(arg));
+				}
+				Some(arg) => break arg,
+				None => return Err(MinigrepError::MissingQuery),
+			}
 		};
 
+		// Every remaining argument is a file to search
+		let file_paths: Vec<String> = args.collect();
+		if file_paths.is_empty() {
+			return Err(MinigrepError::MissingFilePath);
+		}
+
 		// "var" returns Ok() with contents of the value of the environment 
 		// variable, otherwise it will return Err() if the environment variable
 		// does not exist. "is_ok" returns true if Ok() and returns false if 
@@ -33,27 +89,54 @@ impl Config {
 
 		Ok(Config { 
 			query, 
-			file_path,
+			file_paths,
 			ignore_case,
+			no_messages,
 		})
 	}
 }
 
-// Returning nothing, or any struct that implements "Error" trait
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-	let contents = fs::read_to_string(config.file_path)?;
+// Returning whether any line matched, or the first file error. A file that
+// can't be read doesn't stop the search: its error is printed (unless
+// no_messages is set) and the remaining files are still searched.
+pub fn run(config: &Config) -> Result<bool, MinigrepError> {
+	let mut matched = false;
+	let mut first_error = None;
 
-	let results = if config.ignore_case {
-		search_case_insensitive(&config.query, &contents)
-	} else {
-		search(&config.query, &contents)
-	};
+	for path in &config.file_paths {
+		let contents = match fs::read_to_string(path) {
+			Ok(contents) => contents,
+			Err(source) => {
+				let err = MinigrepError::Io { path: path.clone(), source };
+				if !config.no_messages {
+					eprintln!("minigrep: {err}");
+				}
+				first_error.get_or_insert(err);
+				continue;
+			}
+		};
 
-	for line in results {
-		println!("{line}");
+		let results = if config.ignore_case {
+			search_case_insensitive(&config.query, &contents)
+		} else {
+			search(&config.query, &contents)
+		};
+
+		for line in results {
+			matched = true;
+			// Like grep, only name the file when there is more than one
+			if config.file_paths.len() > 1 {
+				println!("{path}:{line}");
+			} else {
+				println!("{line}");
+			}
+		}
 	}
 
-	Ok(())
+	match first_error {
+		Some(err) => Err(err),
+		None => Ok(matched),
+	}
 }
 
 // We use lifetimes so that the compiler knows which string slice the returned
@@ -82,6 +165,12 @@ pub fn search_case_insensitive<'a>(
 mod tests {
 	use super::*;
 
+	fn args(list: &[&str]) -> impl Iterator<Item = String> {
+		let mut all = vec![String::from("minigrep")];
+		all.extend(list.iter().map(|s| s.to_string()));
+		all.into_iter()
+	}
+
 	#[test]
 	fn case_sensitive() {
 		let query = "duct";
@@ -110,4 +199,71 @@ Trust me.";
 			search_case_insensitive(query, contents)
 		);
 	}	
+
+	#[test]
+	fn build_with_options_and_files() {
+		let config = Config::build(args(&["-s", "to", "a.txt", "b.txt"])).unwrap();
+
+		assert!(config.no_messages);
+		assert_eq!(config.query, "to");
+		assert_eq!(config.file_paths, vec!["a.txt", "b.txt"]);
+	}
+
+	#[test]
+	fn build_dash_dash_ends_options() {
+		let config = Config::build(args(&["--", "-s", "a.txt"])).unwrap();
+
+		assert!(!config.no_messages);
+		assert_eq!(config.query, "-s");
+	}
+
+	#[test]
+	fn build_errors() {
+		assert!(matches!(
+			Config::build(args(&[])),
+			Err(MinigrepError::MissingQuery)
+		));
+		assert!(matches!(
+			Config::build(args(&["to"])),
+			Err(MinigrepError::MissingFilePath)
+		));
+		assert!(matches!(
+			Config::build(args(&["-x", "to", "a.txt"])),
+			Err(MinigrepError::UnknownOption(opt)) if opt == "-x"
+		));
+	}
+
+	#[test]
+	fn run_reports_missing_file_after_searching_others() {
+		let config = Config {
+			query: String::from("nobody"),
+			file_paths: vec![
+				String::from("does-not-exist.txt"),
+				String::from("poem.txt"),
+			],
+			ignore_case: false,
+			no_messages: true,
+		};
+
+		match run(&config) {
+			Err(MinigrepError::Io { path, .. }) => {
+				assert_eq!(path, "does-not-exist.txt")
+			}
+			_ => panic!("expected an I/O error for the missing file"),
+		}
+	}
+
+	#[test]
+	fn run_match_and_no_match() {
+		let mut config = Config {
+			query: String::from("frog"),
+			file_paths: vec![String::from("poem.txt")],
+			ignore_case: false,
+			no_messages: false,
+		};
+		assert!(run(&config).unwrap());
+
+		config.query = String::from("not in the poem");
+		assert!(!run(&config).unwrap());
+	}
 }
@@ -1,5 +1,5 @@
 use std::{env, process};
-use minigrep::Config;
+use minigrep::{Config, EXIT_ERROR, EXIT_MATCH, EXIT_NO_MATCH};
 
 fn main() {
 	// "unwrap_or_else" will return the contents inside Ok(), or take the 
@@ -7,14 +7,16 @@ fn main() {
 	// function.	
 	let config = Config::build(env::args()).unwrap_or_else(|err| {
 		eprintln!("Problem parsing arguments: {err}");
-		process::exit(1);
+		eprintln!("Usage: minigrep [-s|--no-messages] QUERY FILE...");
+		process::exit(EXIT_ERROR);
 	});
 
-	// If an error is returned from run, handle it.
-	// We use "if let" instead of "unwrap_or_else" because we dont care about
-	// unwrapping the Ok(()) case, we only want to handle an error.
-	if let Err(e) = minigrep::run(config) {
-		eprintln!("Application error: {e}");
-		process::exit(1);
-	}
+	// Exit like grep does. File errors were already reported by run (unless
+	// -s was given), so here we only pick the exit code.
+	let code = match minigrep::run(&config) {
+		Ok(true) => EXIT_MATCH,
+		Ok(false) => EXIT_NO_MATCH,
+		Err(_) => EXIT_ERROR,
+	};
+	process::exit(code);
 }
@@ -0,0 +1,510 @@
+//! What every kind of content has besides its summary: an id, an author,
+//! when it was published and its tags. The Content trait gives access to
+//! them through each type's Metadata, and the same builder methods set them
+//! on any type.
+//!
+//! The author lives in Metadata rather than on the trait, because Summary
+//! already has an author method and a second one would make every call to
+//! it ambiguous. Summary::author returns the same name for all our types.
+//!
+//! Metadata can only be changed through the builder methods, which tidy it
+//! up, so Content is sealed to the types here.
+
+use crate::{NewsArticle, Summary, Tweet};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub(crate) id: String,
+    pub(crate) author: String,
+    pub(crate) published: Option<SystemTime>,
+    pub(crate) tags: Vec<String>,
+}
+
+impl Metadata {
+    pub(crate) fn new(author: &str) -> Self {
+        Self {
+            author: author.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Empty until one is given. Anything unique to the source will do,
+    /// such as a URL or a feed's guid.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    pub fn published(&self) -> Option<SystemTime> {
+        self.published
+    }
+
+    /// Lowercase, without a leading '#', each only once.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+mod sealed {
+    use super::Metadata;
+
+    pub trait Sealed {
+        fn metadata_mut(&mut self) -> &mut Metadata;
+    }
+}
+
+pub trait Content: Summary + sealed::Sealed {
+    fn metadata(&self) -> &Metadata;
+
+    fn id(&self) -> &str {
+        &self.metadata().id
+    }
+
+    fn published(&self) -> Option<SystemTime> {
+        self.metadata().published
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.metadata().tags
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags().contains(&normalize_tag(tag))
+    }
+
+    fn with_id(mut self, id: &str) -> Self
+    where
+        Self: Sized,
+    {
+        self.metadata_mut().id = id.to_string();
+        self
+    }
+
+    fn with_published(mut self, published: SystemTime) -> Self
+    where
+        Self: Sized,
+    {
+        self.metadata_mut().published = Some(published);
+        self
+    }
+
+    /// Add tags, tidied up: "#Hockey" and "hockey" are the same tag, and
+    /// blank ones are dropped.
+    fn with_tags(mut self, tags: &[&str]) -> Self
+    where
+        Self: Sized,
+    {
+        let existing = &mut self.metadata_mut().tags;
+        for tag in tags.iter().map(|tag| normalize_tag(tag)) {
+            if !tag.is_empty() && !existing.contains(&tag) {
+                existing.push(tag);
+            }
+        }
+        self
+    }
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').trim().to_lowercase()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentError {
+    /// A field that can't be blank was.
+    Empty(&'static str),
+    /// Not an http or https URL.
+    BadUrl(String),
+    /// Episodes and videos have to last at least a second.
+    NoDuration,
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentError::Empty(field) => write!(f, "{field} can't be empty"),
+            ContentError::BadUrl(url) => write!(f, "{url:?} isn't an http or https URL"),
+            ContentError::NoDuration => write!(f, "duration can't be under a second"),
+        }
+    }
+}
+
+impl Error for ContentError {}
+
+fn required(field: &'static str, value: &str) -> Result<String, ContentError> {
+    let value = value.trim();
+    if value.is_empty() {
+        Err(ContentError::Empty(field))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+fn url(url: &str) -> Result<String, ContentError> {
+    let url = url.trim();
+    let rest = ["https://", "http://"].into_iter().find_map(|scheme| {
+        url.get(..scheme.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(scheme))
+            .map(|_| &url[scheme.len()..])
+    });
+    match rest {
+        Some(rest) if !rest.is_empty() && !rest.contains(char::is_whitespace) => {
+            Ok(url.to_string())
+        }
+        _ => Err(ContentError::BadUrl(url.to_string())),
+    }
+}
+
+/// A duration as h:mm:ss, or m:ss under an hour.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlogPost {
+    title: String,
+    url: String,
+    body: String,
+    meta: Metadata,
+}
+
+impl BlogPost {
+    pub fn new(title: &str, author: &str, url: &str, body: &str) -> Result<Self, ContentError> {
+        Ok(Self {
+            title: required("title", title)?,
+            url: self::url(url)?,
+            body: body.to_string(),
+            meta: Metadata::new(&required("author", author)?),
+        })
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
+
+impl Summary for BlogPost {
+    fn summarize(&self) -> String {
+        format!("{}, by {}", self.title, self.meta.author)
+    }
+
+    fn author(&self) -> Option<&str> {
+        Some(&self.meta.author)
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "title" => Some(self.title.clone()),
+            "url" => Some(self.url.clone()),
+            "content" => Some(self.body.clone()),
+            _ => None,
+        }
+    }
+
+    fn default_template(&self) -> &'static str {
+        "{title}[, by {author}]"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PodcastEpisode {
+    show: String,
+    title: String,
+    duration: Duration,
+    audio_url: String,
+    meta: Metadata,
+}
+
+impl PodcastEpisode {
+    /// host is the episode's author.
+    pub fn new(
+        show: &str,
+        title: &str,
+        host: &str,
+        duration: Duration,
+        audio_url: &str,
+    ) -> Result<Self, ContentError> {
+        // Anything shorter would show as 0:00
+        if duration.as_secs() == 0 {
+            return Err(ContentError::NoDuration);
+        }
+        Ok(Self {
+            show: required("show", show)?,
+            title: required("title", title)?,
+            duration,
+            audio_url: url(audio_url)?,
+            meta: Metadata::new(&required("host", host)?),
+        })
+    }
+
+    pub fn show(&self) -> &str {
+        &self.show
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn audio_url(&self) -> &str {
+        &self.audio_url
+    }
+}
+
+impl Summary for PodcastEpisode {
+    fn summarize(&self) -> String {
+        format!(
+            "{}: {} ({})",
+            self.show,
+            self.title,
+            format_duration(self.duration)
+        )
+    }
+
+    fn author(&self) -> Option<&str> {
+        Some(&self.meta.author)
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "show" => Some(self.show.clone()),
+            "title" => Some(self.title.clone()),
+            "duration" => Some(format_duration(self.duration)),
+            "url" => Some(self.audio_url.clone()),
+            _ => None,
+        }
+    }
+
+    fn default_template(&self) -> &'static str {
+        "{show}: {title}[ ({duration})]"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Video {
+    title: String,
+    url: String,
+    duration: Duration,
+    meta: Metadata,
+}
+
+impl Video {
+    pub fn new(
+        title: &str,
+        author: &str,
+        url: &str,
+        duration: Duration,
+    ) -> Result<Self, ContentError> {
+        if duration.as_secs() == 0 {
+            return Err(ContentError::NoDuration);
+        }
+        Ok(Self {
+            title: required("title", title)?,
+            url: self::url(url)?,
+            duration,
+            meta: Metadata::new(&required("author", author)?),
+        })
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl Summary for Video {
+    fn summarize(&self) -> String {
+        format!(
+            "{} by {} [{}]",
+            self.title,
+            self.meta.author,
+            format_duration(self.duration)
+        )
+    }
+
+    fn author(&self) -> Option<&str> {
+        Some(&self.meta.author)
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "title" => Some(self.title.clone()),
+            "url" => Some(self.url.clone()),
+            "duration" => Some(format_duration(self.duration)),
+            _ => None,
+        }
+    }
+
+    fn default_template(&self) -> &'static str {
+        "{title}[ by {author}][ \\[{duration}\\]]"
+    }
+}
+
+macro_rules! impl_content {
+    ($($content:ty),*) => {
+        $(
+            impl Content for $content {
+                fn metadata(&self) -> &Metadata {
+                    &self.meta
+                }
+            }
+
+            impl sealed::Sealed for $content {
+                fn metadata_mut(&mut self) -> &mut Metadata {
+                    &mut self.meta
+                }
+            }
+        )*
+    };
+}
+
+impl_content!(NewsArticle, Tweet, BlogPost, PodcastEpisode, Video);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{render, SummaryOptions};
+    use crate::TweetType;
+    use std::time::UNIX_EPOCH;
+
+    fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    #[test]
+    fn validates_constructors() {
+        assert!(BlogPost::new("Hello", "ada", "https://ada.example/hello", "").is_ok());
+        assert_eq!(
+            BlogPost::new("  ", "ada", "https://ada.example/", ""),
+            Err(ContentError::Empty("title"))
+        );
+        assert_eq!(
+            BlogPost::new("Hello", "ada", "ftp://ada.example/", ""),
+            Err(ContentError::BadUrl(String::from("ftp://ada.example/")))
+        );
+        assert_eq!(
+            Video::new("Clip", "ada", "https://", minutes(1)),
+            Err(ContentError::BadUrl(String::from("https://")))
+        );
+        assert_eq!(
+            Video::new("Clip", "ada", "https://v.example/1", Duration::ZERO),
+            Err(ContentError::NoDuration)
+        );
+        assert_eq!(
+            Video::new(
+                "Clip",
+                "ada",
+                "https://v.example/1",
+                Duration::from_millis(400)
+            ),
+            Err(ContentError::NoDuration)
+        );
+        assert_eq!(
+            PodcastEpisode::new(
+                "Show",
+                "Ep 1",
+                "h",
+                Duration::from_millis(999),
+                "https://p.example/1.mp3"
+            ),
+            Err(ContentError::NoDuration)
+        );
+        assert_eq!(
+            PodcastEpisode::new("Show", "Ep 1", "", minutes(30), "https://p.example/1.mp3"),
+            Err(ContentError::Empty("host"))
+        );
+    }
+
+    #[test]
+    fn summarizes_new_types() {
+        let post =
+            BlogPost::new("Hello, world", "ada", "https://ada.example/hello", "Hi.").unwrap();
+        let episode = PodcastEpisode::new(
+            "Hockey Talk",
+            "Cup final recap",
+            "Mike",
+            minutes(63) + Duration::from_secs(5),
+            "https://p.example/42.mp3",
+        )
+        .unwrap();
+        let video = Video::new(
+            "Goal of the year",
+            "nhl",
+            "https://v.example/goal",
+            minutes(2),
+        )
+        .unwrap();
+
+        assert_eq!(post.summarize(), "Hello, world, by ada");
+        assert_eq!(
+            episode.summarize(),
+            "Hockey Talk: Cup final recap (1:03:05)"
+        );
+        assert_eq!(video.summarize(), "Goal of the year by nhl [2:00]");
+        assert_eq!(post.excerpt(1), "Hi.");
+
+        // Default templates come out the same as summarize
+        let options = SummaryOptions::default();
+        assert_eq!(render(&post, &options), post.summarize());
+        assert_eq!(render(&episode, &options), episode.summarize());
+        assert_eq!(render(&video, &options), video.summarize());
+    }
+
+    #[test]
+    fn shares_metadata() {
+        let published = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let article = NewsArticle::new("Penguins win", "Pittsburgh", "Iceburgh", "")
+            .with_id("wire-1")
+            .with_published(published)
+            .with_tags(&["#Hockey", "hockey", " NHL ", ""]);
+        let tweet = Tweet::new("dril", "no", TweetType::Tweet, None)
+            .unwrap()
+            .with_tags(&["#no"]);
+
+        let items: [&dyn Content; 2] = [&article, &tweet];
+        assert_eq!(items[0].id(), "wire-1");
+        assert_eq!(items[0].published(), Some(published));
+        assert_eq!(items[0].tags(), ["hockey", "nhl"]);
+        assert!(items[0].has_tag("#NHL"));
+        assert_eq!(items[1].id(), "");
+        assert_eq!(items[1].published(), None);
+        assert_eq!(items[1].metadata().author(), "dril");
+        assert_eq!(items[1].author(), Some("dril"));
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::from_secs(5)), "0:05");
+        assert_eq!(format_duration(minutes(59)), "59:00");
+        assert_eq!(format_duration(minutes(600)), "10:00:00");
+    }
+}
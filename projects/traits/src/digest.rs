@@ -0,0 +1,302 @@
+//! Digests: a titled list of summaries rendered as one document, to email
+//! or publish. Each Renderer produces a different format, and escapes the
+//! text it's given so a headline can't break the markup around it.
+
+use crate::feed::Feed;
+use crate::Summary;
+
+/// One item in a digest, and where it came from if that's known.
+pub struct DigestItem<'a> {
+    pub item: &'a dyn Summary,
+    pub source: Option<&'a str>,
+}
+
+impl DigestItem<'_> {
+    // The item's body, unless the summary already says it all, as a
+    // tweet's does
+    fn content(&self) -> Option<String> {
+        self.item.field("content").filter(|content| {
+            let content = content.trim();
+            !content.is_empty() && !self.item.summarize().contains(content)
+        })
+    }
+}
+
+pub struct Digest<'a> {
+    pub title: String,
+    pub items: Vec<DigestItem<'a>>,
+}
+
+impl<'a> Digest<'a> {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Everything in feed, newest first, with its source.
+    pub fn from_feed(title: &str, feed: &'a Feed) -> Self {
+        let mut digest = Self::new(title);
+        digest.items = feed
+            .iter()
+            .map(|entry| DigestItem {
+                item: entry.item.as_ref(),
+                source: Some(&entry.source),
+            })
+            .collect();
+        digest
+    }
+
+    pub fn push(&mut self, item: &'a dyn Summary) {
+        self.items.push(DigestItem { item, source: None });
+    }
+}
+
+pub trait Renderer {
+    fn render(&self, digest: &Digest<'_>) -> String;
+}
+
+/// A standalone HTML page.
+pub struct Html;
+
+/// CommonMark.
+pub struct Markdown;
+
+pub struct PlainText;
+
+impl Renderer for Html {
+    fn render(&self, digest: &Digest<'_>) -> String {
+        let title = escape_html(&digest.title);
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n"
+        );
+
+        if digest.items.is_empty() {
+            out.push_str("<p>Nothing new.</p>\n");
+        } else {
+            out.push_str("<ul>\n");
+            for entry in &digest.items {
+                out.push_str("<li>\n");
+                let summary = escape_html(&entry.item.summarize());
+                out.push_str(&format!("<p><strong>{summary}</strong></p>\n"));
+                if let Some(content) = entry.content() {
+                    for paragraph in paragraphs(&content) {
+                        out.push_str(&format!("<p>{}</p>\n", escape_html(&paragraph)));
+                    }
+                }
+                if let Some(source) = entry.source {
+                    let source = escape_html(source);
+                    out.push_str(&format!("<p class=\"source\">via {source}</p>\n"));
+                }
+                out.push_str("</li>\n");
+            }
+            out.push_str("</ul>\n");
+        }
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+impl Renderer for Markdown {
+    fn render(&self, digest: &Digest<'_>) -> String {
+        let mut out = format!("# {}\n\n", escape_markdown(&digest.title));
+
+        if digest.items.is_empty() {
+            out.push_str("Nothing new.\n");
+        }
+        for entry in &digest.items {
+            out.push_str(&format!(
+                "- **{}**",
+                escape_markdown(&entry.item.summarize())
+            ));
+            if let Some(source) = entry.source {
+                out.push_str(&format!(" _(via {})_", escape_markdown(source)));
+            }
+            out.push('\n');
+            // Indented to stay inside the list item
+            if let Some(content) = entry.content() {
+                for paragraph in paragraphs(&content) {
+                    out.push_str(&format!("\n  {}\n", escape_markdown(&paragraph)));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl Renderer for PlainText {
+    fn render(&self, digest: &Digest<'_>) -> String {
+        let title = plain(&digest.title);
+        let rule = "=".repeat(title.chars().count());
+        let mut out = format!("{title}\n{rule}\n\n");
+
+        if digest.items.is_empty() {
+            out.push_str("Nothing new.\n");
+        }
+        for entry in &digest.items {
+            out.push_str(&format!("* {}", plain(&entry.item.summarize())));
+            if let Some(source) = entry.source {
+                out.push_str(&format!(" (via {})", plain(source)));
+            }
+            out.push('\n');
+            if let Some(content) = entry.content() {
+                for paragraph in paragraphs(&content) {
+                    out.push_str(&format!("  {}\n", plain(&paragraph)));
+                }
+            }
+        }
+        out
+    }
+}
+
+// Blank lines split paragraphs, and other line breaks become spaces
+fn paragraphs(text: &str) -> Vec<String> {
+    text.split("\n\n")
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect()
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Backslash every character that could start Markdown formatting or an
+/// entity, and fold line breaks into spaces so text can't start a new block.
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~' | '!' | '(' | ')'
+            | '&' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' | '\r' => out.push(' '),
+            _ => out.push(c),
+        }
+    }
+    // A list marker at the start would still be read as one. Parentheses
+    // are escaped already, so a numbered item only needs its period escaped.
+    if out.starts_with(['-', '+']) {
+        out.insert(0, '\\');
+    }
+    let digits = out.bytes().take_while(u8::is_ascii_digit).count();
+    let marker = out[digits..]
+        .strip_prefix('.')
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']));
+    if (1..=9).contains(&digits) && marker {
+        out.insert(digits, '\\');
+    }
+    out
+}
+
+// Line breaks and other control characters would throw out the layout
+fn plain(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewsArticle, Tweet, TweetType};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn article() -> NewsArticle {
+        NewsArticle::new(
+            "Q&A: <script> tags & you",
+            "Web",
+            "O'Neil",
+            "First paragraph.\n\nSecond *paragraph*\nwith a break.",
+        )
+    }
+
+    fn tweet() -> Tweet {
+        Tweet::new("horse_ebooks", "of course", TweetType::Tweet, None).unwrap()
+    }
+
+    #[test]
+    fn renders_html() {
+        let article = article();
+        let tweet = tweet();
+        let mut digest = Digest::new("Daily <digest>");
+        digest.push(&article);
+        digest.push(&tweet);
+
+        let html = Html.render(&digest);
+        assert!(html.contains("<title>Daily &lt;digest&gt;</title>"));
+        assert!(html.contains(
+            "<p><strong>Q&amp;A: &lt;script&gt; tags &amp; you, by O&#39;Neil (Web)</strong></p>"
+        ));
+        assert!(html.contains("<p>First paragraph.</p>\n<p>Second *paragraph* with a break.</p>"));
+        assert!(html.contains("<p><strong>horse_ebooks: of course</strong></p>\n</li>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.ends_with("</ul>\n</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn renders_markdown() {
+        let article = article();
+        let mut digest = Digest::new("Daily digest");
+        digest.push(&article);
+
+        assert_eq!(
+            Markdown.render(&digest),
+            "# Daily digest\n\n\
+             - **Q\\&A: \\<script\\> tags \\& you, by O'Neil \\(Web\\)**\n\n  \
+             First paragraph.\n\n  \
+             Second \\*paragraph\\* with a break.\n\n"
+        );
+        assert_eq!(escape_markdown("- not a list"), "\\- not a list");
+        assert_eq!(escape_markdown("1999. A year"), "1999\\. A year");
+        assert_eq!(escape_markdown("1999) A year"), "1999\\) A year");
+        assert_eq!(escape_markdown("7."), "7\\.");
+        assert_eq!(escape_markdown("3.5 million fans"), "3.5 million fans");
+        assert_eq!(escape_markdown("a\nb"), "a b");
+        assert_eq!(
+            escape_markdown("AT&amp;T &lt;b&gt;"),
+            "AT\\&amp;T \\&lt;b\\&gt;"
+        );
+    }
+
+    #[test]
+    fn renders_plain_text_from_a_feed() {
+        let mut feed = Feed::new();
+        feed.push("wire", UNIX_EPOCH + Duration::from_secs(1), article());
+        feed.push("social", UNIX_EPOCH + Duration::from_secs(2), tweet());
+
+        let digest = Digest::from_feed("Today", &feed);
+        assert_eq!(
+            PlainText.render(&digest),
+            "Today\n=====\n\n\
+             * horse_ebooks: of course (via social)\n\
+             * Q&A: <script> tags & you, by O'Neil (Web) (via wire)\n  \
+             First paragraph.\n  Second *paragraph* with a break.\n"
+        );
+    }
+
+    #[test]
+    fn says_when_empty() {
+        let digest = Digest::new("Quiet day");
+        assert!(Html.render(&digest).contains("<p>Nothing new.</p>"));
+        assert!(Markdown.render(&digest).ends_with("Nothing new.\n"));
+        assert!(PlainText.render(&digest).ends_with("Nothing new.\n"));
+    }
+}
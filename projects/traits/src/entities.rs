@@ -0,0 +1,312 @@
+//! Hashtags, @mentions and links in tweet text, found the way the platform
+//! finds them and reported with byte ranges into the text.
+//!
+//! A hashtag is `#` and then letters, digits, marks or underscores, with at
+//! least one that isn't a digit, in any script. A mention is `@` and up to
+//! 15 ASCII letters, digits or underscores, and not followed by any other
+//! letter. Neither counts straight after a letter or digit, so an email
+//! address isn't a mention. Links are http and https URLs, and a `#` or `@`
+//! inside one is part of the link.
+
+use crate::content::Content;
+use crate::length::find_urls;
+use crate::Tweet;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::time::{Duration, SystemTime};
+
+const MAX_MENTION: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+    Hashtag,
+    Mention,
+    Url,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entity<'a> {
+    pub kind: EntityKind,
+    /// Where the entity is in the text, `#` or `@` included.
+    pub range: Range<usize>,
+    /// The text in range.
+    pub text: &'a str,
+}
+
+impl<'a> Entity<'a> {
+    /// The hashtag or username without its `#` or `@`, or the whole URL.
+    pub fn name(&self) -> &'a str {
+        match self.kind {
+            EntityKind::Url => self.text,
+            _ => {
+                let mut chars = self.text.chars();
+                chars.next();
+                chars.as_str()
+            }
+        }
+    }
+}
+
+/// Every entity in text, in order.
+pub fn extract(text: &str) -> Vec<Entity<'_>> {
+    let urls = find_urls(text);
+    let mut entities: Vec<Entity<'_>> = urls
+        .iter()
+        .map(|range| Entity {
+            kind: EntityKind::Url,
+            range: range.clone(),
+            text: &text[range.clone()],
+        })
+        .collect();
+
+    let mut previous = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let after_word =
+            previous.is_some_and(|before: char| before.is_alphanumeric() || before == '_');
+        previous = Some(c);
+        let kind = match c {
+            '#' | '＃' => EntityKind::Hashtag,
+            '@' | '＠' => EntityKind::Mention,
+            _ => continue,
+        };
+        if after_word || urls.iter().any(|url| url.contains(&start)) {
+            continue;
+        }
+
+        let name_start = start + c.len_utf8();
+        let mut end = name_start;
+        let mut length = 0;
+        while let Some(&(at, next)) = chars.peek() {
+            let fits = match kind {
+                EntityKind::Mention => next.is_ascii_alphanumeric() || next == '_',
+                _ => is_hashtag_char(next),
+            };
+            if !fits {
+                break;
+            }
+            end = at + next.len_utf8();
+            length += 1;
+            previous = Some(next);
+            chars.next();
+        }
+
+        let name = &text[name_start..end];
+        let valid = match kind {
+            EntityKind::Mention => (1..=MAX_MENTION).contains(&length),
+            _ => name.chars().any(|c| !c.is_numeric()),
+        };
+        // A name running into another sigil, as in "#a#b", is neither, and
+        // "@useré" isn't @user
+        let run_on = text[end..].starts_with(|next: char| {
+            matches!(next, '#' | '＃' | '@' | '＠') || next.is_alphanumeric()
+        });
+        if valid && !run_on {
+            entities.push(Entity {
+                kind,
+                range: start..end,
+                text: &text[start..end],
+            });
+        }
+    }
+
+    entities.sort_by_key(|entity| entity.range.start);
+    entities
+}
+
+// Letters and digits in any script, the marks that combine with them, and
+// the joiners some scripts need inside a word
+fn is_hashtag_char(c: char) -> bool {
+    c.is_alphanumeric()
+        || c == '_'
+        || matches!(
+            c,
+            '\u{200C}' | '\u{200D}' | '\u{0300}'..='\u{036F}' | '\u{3099}'..='\u{309A}'
+        )
+        // Indic vowel signs and viramas, but not the dandas that end sentences
+        || matches!(c, '\u{0900}'..='\u{0DFF}') && !matches!(c, '\u{0964}' | '\u{0965}')
+}
+
+impl Tweet {
+    pub fn entities(&self) -> Vec<Entity<'_>> {
+        extract(&self.content)
+    }
+
+    /// The tweet's hashtags, without the `#`, each once, in order.
+    pub fn hashtags(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.entities()
+            .into_iter()
+            .filter(|entity| entity.kind == EntityKind::Hashtag)
+            .map(|entity| entity.name())
+            .filter(|name| seen.insert(name.to_lowercase()))
+            .collect()
+    }
+
+    /// Usernames the tweet mentions, without the `@`, in order.
+    pub fn mentions(&self) -> Vec<&str> {
+        self.entities()
+            .into_iter()
+            .filter(|entity| entity.kind == EntityKind::Mention)
+            .map(|entity| entity.name())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trend {
+    /// Lowercased, without the `#`.
+    pub hashtag: String,
+    pub tweets: usize,
+    /// How many people used it, which counts for more than how often.
+    pub authors: usize,
+}
+
+/// The limit most used hashtags in tweets published in the window up to
+/// now. Hashtags are compared ignoring case, and ranked by how many
+/// authors used them, so one account repeating a tag can't trend it alone,
+/// then by how many tweets did. Tweets without a publication time are
+/// skipped.
+pub fn trending<'a>(
+    tweets: impl IntoIterator<Item = &'a Tweet>,
+    now: SystemTime,
+    window: Duration,
+    limit: usize,
+) -> Vec<Trend> {
+    let since = now.checked_sub(window);
+    let mut usage: HashMap<String, (usize, HashSet<&str>)> = HashMap::new();
+
+    for tweet in tweets {
+        let Some(published) = tweet.published() else {
+            continue;
+        };
+        if published > now || since.is_some_and(|since| published <= since) {
+            continue;
+        }
+        for hashtag in tweet.hashtags() {
+            let (count, authors) = usage.entry(hashtag.to_lowercase()).or_default();
+            *count += 1;
+            authors.insert(tweet.username());
+        }
+    }
+
+    let mut trends: Vec<Trend> = usage
+        .into_iter()
+        .map(|(hashtag, (tweets, authors))| Trend {
+            hashtag,
+            tweets,
+            authors: authors.len(),
+        })
+        .collect();
+    trends.sort_by(|a, b| {
+        (b.authors, b.tweets)
+            .cmp(&(a.authors, a.tweets))
+            .then_with(|| a.hashtag.cmp(&b.hashtag))
+    });
+    trends.truncate(limit);
+    trends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TweetType;
+    use std::time::UNIX_EPOCH;
+
+    fn found(text: &str) -> Vec<(EntityKind, &str)> {
+        extract(text)
+            .into_iter()
+            .map(|entity| (entity.kind, entity.text))
+            .collect()
+    }
+
+    fn tweet(username: &str, content: &str, secs: u64) -> Tweet {
+        Tweet::new(username, content, TweetType::Tweet, None)
+            .unwrap()
+            .with_published(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn finds_entities_with_byte_ranges() {
+        let text = "Go #Pens! @dril see https://nhl.example/a#b?x=@y";
+        let entities = extract(text);
+        assert_eq!(entities.len(), 3);
+        assert_eq!(entities[0].range, 3..8);
+        assert_eq!(entities[0].name(), "Pens");
+        assert_eq!(entities[1].kind, EntityKind::Mention);
+        assert_eq!(&text[entities[1].range.clone()], "@dril");
+        assert_eq!(entities[2].kind, EntityKind::Url);
+        assert_eq!(entities[2].name(), "https://nhl.example/a#b?x=@y");
+    }
+
+    #[test]
+    fn respects_word_boundaries() {
+        assert!(found("mail me@example.com or a#b").is_empty());
+        assert!(found("#123 and #1st").contains(&(EntityKind::Hashtag, "#1st")));
+        assert_eq!(found("#123 and #1st").len(), 1);
+        assert!(found("#a#b @x@y").is_empty());
+        assert!(found("@abcdefghijklmnop is too long").is_empty());
+        assert!(found("@useré @user名").is_empty());
+        assert_eq!(
+            found("(@dril), @dril's #tag_ok."),
+            [
+                (EntityKind::Mention, "@dril"),
+                (EntityKind::Mention, "@dril"),
+                (EntityKind::Hashtag, "#tag_ok"),
+            ]
+        );
+    }
+
+    #[test]
+    fn handles_other_scripts() {
+        let text = "今日は ＃東京 と #café で #हिन्दी ＠user";
+        assert_eq!(
+            found(text),
+            [
+                (EntityKind::Hashtag, "＃東京"),
+                (EntityKind::Hashtag, "#café"),
+                (EntityKind::Hashtag, "#हिन्दी"),
+                (EntityKind::Mention, "＠user"),
+            ]
+        );
+        let entities = extract(text);
+        assert!(entities
+            .iter()
+            .all(|entity| text.is_char_boundary(entity.range.start)
+                && text.is_char_boundary(entity.range.end)));
+        assert_eq!(entities[0].name(), "東京");
+        assert_eq!(entities[3].name(), "user");
+    }
+
+    #[test]
+    fn lists_hashtags_and_mentions() {
+        let tweet = tweet("a", "#Pens win! #pens @dril @wint", 0);
+        assert_eq!(tweet.hashtags(), ["Pens"]);
+        assert_eq!(tweet.mentions(), ["dril", "wint"]);
+    }
+
+    #[test]
+    fn trends_over_a_window() {
+        let tweets = [
+            tweet("a", "#Pens win", 100),
+            tweet("b", "#pens #cup", 110),
+            tweet("c", "go #PENS", 120),
+            tweet("spam", "#deals #deals", 121),
+            tweet("spam", "#deals", 122),
+            tweet("spam", "#deals", 123),
+            tweet("d", "#cup", 130),
+            tweet("e", "#old", 10),
+            tweet("f", "#future", 500),
+        ];
+        let now = UNIX_EPOCH + Duration::from_secs(200);
+        let trends = trending(&tweets, now, Duration::from_secs(120), 10);
+
+        let ranked: Vec<(&str, usize, usize)> = trends
+            .iter()
+            .map(|trend| (trend.hashtag.as_str(), trend.authors, trend.tweets))
+            .collect();
+        assert_eq!(ranked, [("pens", 3, 3), ("cup", 2, 2), ("deals", 1, 3)]);
+        assert_eq!(trending(&tweets, now, Duration::from_secs(120), 1).len(), 1);
+        assert!(trending(&tweets, now, Duration::ZERO, 10).is_empty());
+    }
+}
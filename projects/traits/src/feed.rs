@@ -0,0 +1,249 @@
+//! A single timeline of items from many sources. Anything that implements
+//! Summary can go in, so articles and tweets sit side by side.
+
+use crate::similar::Detector;
+use crate::Summary;
+use std::collections::HashSet;
+use std::time::SystemTime;
+
+/// One entry in a Feed: the item, plus where and when it was published.
+pub struct FeedItem {
+    pub published: SystemTime,
+    pub source: String,
+    pub item: Box<dyn Summary>,
+}
+
+impl FeedItem {
+    // Two items are the same story if their summaries match, ignoring case
+    // and spacing.
+    fn dedup_key(&self) -> String {
+        let summary = self.item.summarize().to_lowercase();
+        summary.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Items from any number of sources, kept newest first.
+#[derive(Default)]
+pub struct Feed {
+    items: Vec<FeedItem>,
+}
+
+impl Feed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, source: &str, published: SystemTime, item: impl Summary + 'static) {
+        self.push_boxed(source, published, Box::new(item));
+    }
+
+    pub fn push_boxed(&mut self, source: &str, published: SystemTime, item: Box<dyn Summary>) {
+        // After everything at least as new, so items published at the same
+        // time stay in the order they arrived
+        let at = self
+            .items
+            .partition_point(|existing| existing.published >= published);
+        self.items.insert(
+            at,
+            FeedItem {
+                published,
+                source: source.to_string(),
+                item,
+            },
+        );
+    }
+
+    /// Move every item of other into this feed, keeping it newest first. On
+    /// equal times this feed's items come first.
+    pub fn merge(&mut self, other: Feed) {
+        let mut ours = std::mem::take(&mut self.items).into_iter().peekable();
+        let mut theirs = other.items.into_iter().peekable();
+
+        loop {
+            let next = match (ours.peek(), theirs.peek()) {
+                (Some(a), Some(b)) if a.published >= b.published => ours.next(),
+                (Some(_), Some(_)) => theirs.next(),
+                (Some(_), None) => ours.next(),
+                (None, Some(_)) => theirs.next(),
+                (None, None) => break,
+            };
+            self.items.extend(next);
+        }
+    }
+
+    /// Drop repeats of the same story, such as one article picked up by
+    /// several sources. The earliest copy is kept. Returns how many items
+    /// were dropped.
+    pub fn dedup(&mut self) -> usize {
+        let before = self.items.len();
+        let mut seen = HashSet::new();
+
+        // Walk oldest first so the first copy seen is the original
+        let mut keep: Vec<bool> = self
+            .items
+            .iter()
+            .rev()
+            .map(|item| seen.insert(item.dedup_key()))
+            .collect();
+        keep.reverse();
+        let mut keep = keep.into_iter();
+        self.items.retain(|_| keep.next().unwrap_or(true));
+
+        before - self.items.len()
+    }
+
+    /// Drop items that tell the same story in different words, keeping the
+    /// canonical copy of each as detector picks it. Returns how many items
+    /// were dropped.
+    pub fn dedup_similar(&mut self, detector: &Detector) -> usize {
+        let before = self.items.len();
+        let clusters = detector.cluster_items(self.items.iter().map(|entry| entry.item.as_ref()));
+
+        let mut keep = vec![false; before];
+        for cluster in clusters {
+            keep[cluster.canonical] = true;
+        }
+        let mut keep = keep.into_iter();
+        self.items.retain(|_| keep.next().unwrap_or(true));
+
+        before - self.items.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Every item, newest first.
+    pub fn iter(&self) -> impl Iterator<Item = &FeedItem> {
+        self.items.iter()
+    }
+
+    /// Page number page (counting from 0) of per_page items, newest first.
+    /// Pages past the end are empty.
+    pub fn page(&self, page: usize, per_page: usize) -> &[FeedItem] {
+        let start = page.saturating_mul(per_page).min(self.items.len());
+        let end = start.saturating_add(per_page).min(self.items.len());
+        &self.items[start..end]
+    }
+
+    /// How many pages of per_page items there are.
+    pub fn page_count(&self, per_page: usize) -> usize {
+        self.items.len().div_ceil(per_page.max(1))
+    }
+
+    /// Items written by author, newest first.
+    pub fn by_author<'a>(&'a self, author: &'a str) -> impl Iterator<Item = &'a FeedItem> {
+        self.items
+            .iter()
+            .filter(move |entry| entry.item.author() == Some(author))
+    }
+
+    /// Items that came from source, newest first.
+    pub fn from_source<'a>(&'a self, source: &'a str) -> impl Iterator<Item = &'a FeedItem> {
+        self.items
+            .iter()
+            .filter(move |entry| entry.source == source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewsArticle, Tweet, TweetType};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn article(headline: &str, author: &str) -> NewsArticle {
+        NewsArticle::new(headline, "Pittsburgh", author, "")
+    }
+
+    fn tweet(username: &str, content: &str) -> Tweet {
+        Tweet::new(username, content, TweetType::Tweet, None).unwrap()
+    }
+
+    fn summaries<'a>(items: impl Iterator<Item = &'a FeedItem>) -> Vec<String> {
+        items.map(|entry| entry.item.summarize()).collect()
+    }
+
+    #[test]
+    fn keeps_newest_first() {
+        let mut feed = Feed::new();
+        feed.push("wire", at(20), article("Penguins win", "Iceburgh"));
+        feed.push("social", at(30), tweet("horse_ebooks", "of course"));
+        feed.push("wire", at(10), article("Snow expected", "Al Roker"));
+        feed.push("social", at(20), tweet("dril", "no"));
+
+        assert_eq!(
+            summaries(feed.iter()),
+            [
+                "horse_ebooks: of course",
+                "Penguins win, by Iceburgh (Pittsburgh)",
+                "dril: no",
+                "Snow expected, by Al Roker (Pittsburgh)",
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_in_order() {
+        let mut wire = Feed::new();
+        wire.push("wire", at(30), article("C", "a"));
+        wire.push("wire", at(10), article("A", "a"));
+        let mut social = Feed::new();
+        social.push("social", at(40), tweet("b", "D"));
+        social.push("social", at(20), tweet("b", "B"));
+        social.push("social", at(10), tweet("b", "A2"));
+
+        wire.merge(social);
+        let times: Vec<SystemTime> = wire.iter().map(|entry| entry.published).collect();
+        assert_eq!(times, [at(40), at(30), at(20), at(10), at(10)]);
+        assert_eq!(wire.page(1, 2)[0].source, "social");
+        assert_eq!(wire.iter().nth(3).unwrap().source, "wire");
+    }
+
+    #[test]
+    fn dedups_keeping_the_original() {
+        let mut feed = Feed::new();
+        feed.push("wire", at(10), article("Penguins win", "Iceburgh"));
+        feed.push("mirror", at(50), article("PENGUINS  win", "iceburgh"));
+        feed.push("social", at(30), tweet("dril", "no"));
+        feed.push("social", at(40), tweet("dril", "no"));
+
+        assert_eq!(feed.dedup(), 2);
+        assert_eq!(feed.len(), 2);
+        assert_eq!(feed.from_source("wire").count(), 1);
+        assert_eq!(feed.from_source("mirror").count(), 0);
+        assert_eq!(feed.iter().next().unwrap().published, at(30));
+    }
+
+    #[test]
+    fn pages_and_filters() {
+        let mut feed = Feed::new();
+        for i in 0..7 {
+            feed.push(
+                "social",
+                at(i),
+                tweet(if i % 2 == 0 { "even" } else { "odd" }, "hi"),
+            );
+        }
+        feed.push("wire", at(100), article("Headline", "even"));
+
+        assert_eq!(feed.page_count(3), 3);
+        assert_eq!(feed.page(0, 3).len(), 3);
+        assert_eq!(feed.page(2, 3).len(), 2);
+        assert!(feed.page(3, 3).is_empty());
+        assert!(feed.page(0, 0).is_empty());
+
+        assert_eq!(feed.by_author("even").count(), 5);
+        assert_eq!(feed.by_author("odd").count(), 3);
+        assert_eq!(feed.by_author("nobody").count(), 0);
+        assert_eq!(feed.from_source("wire").count(), 1);
+    }
+}
@@ -0,0 +1,659 @@
+//! JSON for storing content and reading it back. Just enough of JSON for
+//! our own types: a Value tree, a parser, and a writer, plus the Json trait
+//! that NewsArticle and Tweet implement. Loading a Tweet runs the same
+//! checks as Tweet::new, so a stored file can't sneak in a tweet that
+//! couldn't have been made.
+
+use crate::content::{Content, Metadata};
+use crate::{NewsArticle, Tweet, TweetError, TweetType};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, UNIX_EPOCH};
+
+// Deeper than this and the input is more likely an attack than content
+const MAX_DEPTH: usize = 128;
+const NANOS: u32 = 1_000_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order they were written.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The member called key, if this is an object that has one.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// Compact JSON, with no spaces or line breaks.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{b}"),
+            // JSON has no infinities or NaN
+            Value::Number(n) if !n.is_finite() => write!(f, "null"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(text) => write_string(f, text),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    /// Not valid JSON.
+    Syntax {
+        offset: usize,
+        message: &'static str,
+    },
+    /// Valid JSON, but a field we need is missing or the wrong type.
+    Field(&'static str),
+    /// A tweet that Tweet::new wouldn't have made.
+    Tweet(TweetError),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Syntax { offset, message } => write!(f, "{message} at {offset}"),
+            JsonError::Field(name) => write!(f, "missing or bad field \"{name}\""),
+            JsonError::Tweet(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::Tweet(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<TweetError> for JsonError {
+    fn from(error: TweetError) -> Self {
+        JsonError::Tweet(error)
+    }
+}
+
+pub fn parse(input: &str) -> Result<Value, JsonError> {
+    let mut parser = Parser { input, at: 0 };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.at < input.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> JsonError {
+        JsonError::Syntax {
+            offset: self.at,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.at).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.at += 1;
+        }
+    }
+
+    fn eat(&mut self, literal: &str) -> bool {
+        if self.input[self.at..].starts_with(literal) {
+            self.at += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ if self.eat("null") => Ok(Value::Null),
+            _ if self.eat("true") => Ok(Value::Bool(true)),
+            _ if self.eat("false") => Ok(Value::Bool(false)),
+            None => Err(self.error("unexpected end")),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.at += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.eat("}") {
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a member name"));
+            }
+            let name = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(":") {
+                return Err(self.error("expected ':'"));
+            }
+            members.push((name, self.value(depth + 1)?));
+            self.skip_whitespace();
+            if self.eat("}") {
+                return Ok(Value::Object(members));
+            } else if !self.eat(",") {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.at += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.eat("]") {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            if self.eat("]") {
+                return Ok(Value::Array(items));
+            } else if !self.eat(",") {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let start = self.at;
+        self.eat("-");
+        let digits = |parser: &mut Self| {
+            let from = parser.at;
+            while matches!(parser.peek(), Some(b'0'..=b'9')) {
+                parser.at += 1;
+            }
+            parser.at > from
+        };
+        if self.input[self.at..].starts_with("0") {
+            self.at += 1;
+        } else if !digits(self) {
+            return Err(self.error("expected a digit"));
+        }
+        if self.eat(".") && !digits(self) {
+            return Err(self.error("expected a digit"));
+        }
+        if self.eat("e") || self.eat("E") {
+            let _ = self.eat("+") || self.eat("-");
+            if !digits(self) {
+                return Err(self.error("expected a digit"));
+            }
+        }
+        let text = &self.input[start..self.at];
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| JsonError::Syntax {
+                offset: start,
+                message: "bad number",
+            })
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.at += 1;
+        let mut out = String::new();
+        loop {
+            let rest = &self.input[self.at..];
+            let Some(c) = rest.chars().next() else {
+                return Err(self.error("unclosed string"));
+            };
+            match c {
+                '"' => {
+                    self.at += 1;
+                    return Ok(out);
+                }
+                '\\' => {
+                    self.at += 1;
+                    out.push(self.escape()?);
+                }
+                c if (c as u32) < 0x20 => return Err(self.error("control character in string")),
+                c => {
+                    out.push(c);
+                    self.at += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    // Just past the backslash
+    fn escape(&mut self) -> Result<char, JsonError> {
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.at += 1;
+                let unit = self.hex4()?;
+                // A high surrogate has to be followed by a low one
+                let code = if (0xD800..0xDC00).contains(&unit) {
+                    if !self.eat("\\u") {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    let low = self.hex4()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    unit
+                };
+                return char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"));
+            }
+            _ => return Err(self.error("bad escape")),
+        };
+        self.at += 1;
+        Ok(c)
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .input
+            .get(self.at..self.at + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("expected four hex digits"))?;
+        self.at += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+}
+
+/// Types that can be stored as JSON and loaded back.
+pub trait Json: Sized {
+    fn to_value(&self) -> Value;
+
+    fn from_value(value: &Value) -> Result<Self, JsonError>;
+
+    fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+
+    fn from_json(input: &str) -> Result<Self, JsonError> {
+        Self::from_value(&parse(input)?)
+    }
+}
+
+impl<T: Json> Json for Vec<T> {
+    fn to_value(&self) -> Value {
+        Value::Array(self.iter().map(Json::to_value).collect())
+    }
+
+    fn from_value(value: &Value) -> Result<Self, JsonError> {
+        match value {
+            Value::Array(items) => items.iter().map(T::from_value).collect(),
+            _ => Err(JsonError::Field("[]")),
+        }
+    }
+}
+
+fn string(value: &Value, name: &'static str) -> Result<String, JsonError> {
+    value
+        .get(name)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or(JsonError::Field(name))
+}
+
+fn text(value: &str) -> Value {
+    Value::String(value.to_string())
+}
+
+// The id, publication time and tags go along when they're set. Times are
+// whole seconds since 1970, earlier ones negative, and the nanoseconds past
+// that second apart, since one f64 can't hold today's times to the
+// nanosecond.
+fn push_metadata(members: &mut Vec<(String, Value)>, meta: &Metadata) {
+    if !meta.id.is_empty() {
+        members.push((String::from("id"), text(&meta.id)));
+    }
+    if let Some(published) = meta.published {
+        let (seconds, nanos) = match published.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as f64, since.subsec_nanos()),
+            Err(before) => {
+                let before = before.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as f64), 0),
+                    nanos => (-(before.as_secs() as f64) - 1.0, NANOS - nanos),
+                }
+            }
+        };
+        members.push((String::from("published"), Value::Number(seconds)));
+        if nanos != 0 {
+            let nanos = Value::Number(f64::from(nanos));
+            members.push((String::from("published_nanos"), nanos));
+        }
+    }
+    if !meta.tags.is_empty() {
+        let tags = meta.tags.iter().map(|tag| text(tag)).collect();
+        members.push((String::from("tags"), Value::Array(tags)));
+    }
+}
+
+fn with_metadata<T: Content>(mut item: T, value: &Value) -> Result<T, JsonError> {
+    if let Some(id) = value.get("id") {
+        item = item.with_id(id.as_str().ok_or(JsonError::Field("id"))?);
+    }
+    let nanos = match value.get("published_nanos") {
+        None | Some(Value::Null) => 0,
+        Some(Value::Number(n)) if n.fract() == 0.0 && (0.0..f64::from(NANOS)).contains(n) => {
+            *n as u64
+        }
+        Some(_) => return Err(JsonError::Field("published_nanos")),
+    };
+    match value.get("published") {
+        None | Some(Value::Null) => (),
+        // Past 2^53 an f64 isn't a whole number of seconds any more
+        Some(Value::Number(seconds))
+            if seconds.fract() == 0.0 && seconds.abs() <= 2f64.powi(53) =>
+        {
+            let whole = Duration::from_secs(seconds.abs() as u64);
+            let published = if *seconds < 0.0 {
+                UNIX_EPOCH.checked_sub(whole)
+            } else {
+                UNIX_EPOCH.checked_add(whole)
+            };
+            let published = published
+                .and_then(|published| published.checked_add(Duration::from_nanos(nanos)))
+                .ok_or(JsonError::Field("published"))?;
+            item = item.with_published(published);
+        }
+        Some(_) => return Err(JsonError::Field("published")),
+    }
+    if let Some(tags) = value.get("tags") {
+        let Value::Array(tags) = tags else {
+            return Err(JsonError::Field("tags"));
+        };
+        let tags: Option<Vec<&str>> = tags.iter().map(Value::as_str).collect();
+        item = item.with_tags(&tags.ok_or(JsonError::Field("tags"))?);
+    }
+    Ok(item)
+}
+
+impl Json for NewsArticle {
+    fn to_value(&self) -> Value {
+        let mut members = vec![
+            (String::from("headline"), text(&self.headline)),
+            (String::from("location"), text(&self.location)),
+            (String::from("author"), text(&self.meta.author)),
+            (String::from("content"), text(&self.content)),
+        ];
+        push_metadata(&mut members, &self.meta);
+        Value::Object(members)
+    }
+
+    fn from_value(value: &Value) -> Result<Self, JsonError> {
+        let article = NewsArticle::new(
+            &string(value, "headline")?,
+            &string(value, "location")?,
+            &string(value, "author")?,
+            &string(value, "content")?,
+        );
+        with_metadata(article, value)
+    }
+}
+
+impl Json for Tweet {
+    fn to_value(&self) -> Value {
+        let tweet_type = match self.tweet_type {
+            TweetType::Tweet => "tweet",
+            TweetType::Retweet => "retweet",
+            TweetType::Reply => "reply",
+        };
+        let replied_to = self
+            .replied_to
+            .map_or(Value::Null, |id| Value::Number(f64::from(id)));
+        let mut members = vec![
+            (String::from("username"), text(&self.meta.author)),
+            (String::from("content"), text(&self.content)),
+            (String::from("type"), text(tweet_type)),
+            (String::from("replied_to"), replied_to),
+        ];
+        push_metadata(&mut members, &self.meta);
+        Value::Object(members)
+    }
+
+    fn from_value(value: &Value) -> Result<Self, JsonError> {
+        let tweet_type = match value.get("type").and_then(Value::as_str) {
+            Some("tweet") => TweetType::Tweet,
+            Some("retweet") => TweetType::Retweet,
+            Some("reply") => TweetType::Reply,
+            _ => return Err(JsonError::Field("type")),
+        };
+        let replied_to = match value.get("replied_to") {
+            None | Some(Value::Null) => None,
+            Some(Value::Number(n))
+                if n.fract() == 0.0 && (0.0..=f64::from(u32::MAX)).contains(n) =>
+            {
+                Some(*n as u32)
+            }
+            Some(_) => return Err(JsonError::Field("replied_to")),
+        };
+        let tweet = Tweet::new(
+            &string(value, "username")?,
+            &string(value, "content")?,
+            tweet_type,
+            replied_to,
+        )?;
+        with_metadata(tweet, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Summary;
+    use std::time::SystemTime;
+
+    #[test]
+    fn parses_and_writes_values() {
+        let value =
+            parse(r#" {"a": [1, -2.5e1, true, null], "b": "x\"\u00e9\ud83d\ude00\n"} "#).unwrap();
+        assert_eq!(
+            value.get("a"),
+            Some(&Value::Array(vec![
+                Value::Number(1.0),
+                Value::Number(-25.0),
+                Value::Bool(true),
+                Value::Null,
+            ]))
+        );
+        assert_eq!(value.get("b").and_then(Value::as_str), Some("x\"é😀\n"));
+        assert_eq!(
+            value.to_string(),
+            "{\"a\":[1,-25,true,null],\"b\":\"x\\\"é😀\\n\"}"
+        );
+        assert_eq!(parse(&value.to_string()), Ok(value));
+    }
+
+    #[test]
+    fn rejects_bad_json() {
+        for bad in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "01",
+            "1.",
+            "\"\\x\"",
+            "\"\\ud800\"",
+            "nul",
+            "1 2",
+            "\"tab\there\"",
+        ] {
+            assert!(
+                matches!(parse(bad), Err(JsonError::Syntax { .. })),
+                "{bad:?} parsed"
+            );
+        }
+        let deep = "[".repeat(MAX_DEPTH + 2);
+        assert_eq!(
+            parse(&deep),
+            Err(JsonError::Syntax {
+                offset: MAX_DEPTH + 1,
+                message: "nested too deeply"
+            })
+        );
+    }
+
+    #[test]
+    fn round_trips_articles() {
+        let articles = vec![
+            NewsArticle::new(
+                "Penguins win",
+                "Pittsburgh, PA",
+                "Iceburgh",
+                "Line one\nline \"two\"",
+            ),
+            NewsArticle::new("", "", "", "")
+                .with_id("wire-1")
+                .with_published(UNIX_EPOCH + Duration::from_secs(1_500_000_000))
+                .with_tags(&["hockey", "nhl"]),
+            NewsArticle::new("", "", "", "").with_published(UNIX_EPOCH - Duration::from_secs(60)),
+            NewsArticle::new("", "", "", "")
+                .with_published(UNIX_EPOCH + Duration::new(1_760_000_000, 123_456_789)),
+            NewsArticle::new("", "", "", "")
+                .with_published(UNIX_EPOCH - Duration::new(1, 250_000_000)),
+            NewsArticle::new("", "", "", "").with_published(SystemTime::now()),
+        ];
+        let json = articles.to_json();
+        assert!(json.contains(r#""published":-2,"published_nanos":750000000"#));
+        assert_eq!(Vec::<NewsArticle>::from_json(&json), Ok(articles));
+        assert_eq!(
+            NewsArticle::from_json(r#"{"headline": "h", "location": "l", "author": 3}"#),
+            Err(JsonError::Field("author"))
+        );
+    }
+
+    #[test]
+    fn round_trips_tweets_with_validation() {
+        let reply = Tweet::new("dril", "no", TweetType::Reply, Some(7)).unwrap();
+        let json = reply.to_json();
+        assert_eq!(
+            json,
+            r#"{"username":"dril","content":"no","type":"reply","replied_to":7}"#
+        );
+        let loaded = Tweet::from_json(&json).unwrap();
+        assert_eq!(loaded.tweet_type(), TweetType::Reply);
+        assert_eq!(loaded.replied_to(), Some(7));
+        assert_eq!(loaded.summarize(), "dril: no");
+
+        let orphan = r#"{"username":"dril","content":"no","type":"reply","replied_to":null}"#;
+        assert_eq!(
+            Tweet::from_json(orphan).err(),
+            Some(JsonError::Tweet(TweetError::ReplyToNothing))
+        );
+        let long = format!(
+            r#"{{"username":"a","content":"{}","type":"tweet"}}"#,
+            "a".repeat(281)
+        );
+        assert_eq!(
+            Tweet::from_json(&long).err(),
+            Some(JsonError::Tweet(TweetError::TooLong { length: 281 }))
+        );
+        let bad_id = r#"{"username":"a","content":"","type":"reply","replied_to":1.5}"#;
+        assert_eq!(
+            Tweet::from_json(bad_id).err(),
+            Some(JsonError::Field("replied_to"))
+        );
+        let tagged = Tweet::new("a", "b", TweetType::Tweet, None)
+            .unwrap()
+            .with_tags(&["#Tag"]);
+        assert_eq!(Tweet::from_json(&tagged.to_json()).unwrap().tags(), ["tag"]);
+        let bad_tags = r#"{"username":"a","content":"","type":"tweet","tags":[1]}"#;
+        assert_eq!(
+            Tweet::from_json(bad_tags).err(),
+            Some(JsonError::Field("tags"))
+        );
+        let bad_type = r#"{"username":"a","content":"","type":"quote"}"#;
+        assert_eq!(
+            Tweet::from_json(bad_type).err(),
+            Some(JsonError::Field("type"))
+        );
+    }
+}
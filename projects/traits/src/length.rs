@@ -0,0 +1,193 @@
+//! Tweet length the way the platform counts it, which is neither bytes nor
+//! chars. Latin, Cyrillic, Greek and other scripts below U+1100, plus some
+//! punctuation, count as 1. Everything else counts as 2, CJK included. An
+//! emoji counts as 2 however many code points it's built from, so skin tones,
+//! flags and ZWJ families don't add up. Links count as URL_LENGTH whatever
+//! their real length, since they get shortened.
+//!
+//! The platform also NFC-normalizes text before counting. We don't, so a
+//! decomposed accent counts as two characters here.
+
+use std::ops::Range;
+
+/// The longest a tweet can be, in weighted characters.
+pub const MAX_LENGTH: usize = 280;
+
+/// What every link counts as, however long it is.
+pub const URL_LENGTH: usize = 23;
+
+/// The length of text as the platform counts it.
+///
+/// ```
+/// use traits::length::weighted_length;
+///
+/// assert_eq!(weighted_length("hello"), 5);
+/// assert_eq!(weighted_length("你好"), 4);
+/// assert_eq!(weighted_length("👍🏽"), 2);
+/// assert_eq!(weighted_length("see https://example.com/a/very/long/path"), 27);
+/// ```
+pub fn weighted_length(text: &str) -> usize {
+    let mut length = 0;
+    let mut from = 0;
+    for url in find_urls(text) {
+        length += text_length(&text[from..url.start]) + URL_LENGTH;
+        from = url.end;
+    }
+    length + text_length(&text[from..])
+}
+
+// Left off the end of a link
+const TRAILING: [char; 10] = ['.', ',', ':', ';', '!', '?', '\'', '"', ')', ']'];
+
+/// Byte ranges of the http and https links in text. Punctuation at the end
+/// of a link is left out, since it's usually the sentence's.
+pub(crate) fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut searched = 0;
+
+    for (start, c) in text.char_indices() {
+        if start < searched || !c.eq_ignore_ascii_case(&'h') {
+            continue;
+        }
+        let at_word_start = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|before| !before.is_alphanumeric());
+        let rest = &text[start..];
+        let scheme = ["https://", "http://"].into_iter().find(|scheme| {
+            rest.get(..scheme.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        });
+        let Some(scheme) = scheme.filter(|_| at_word_start) else {
+            continue;
+        };
+
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let url = rest[..len].trim_end_matches(TRAILING);
+        if url.len() > scheme.len() {
+            urls.push(start..start + url.len());
+        }
+        searched = start + len;
+    }
+    urls
+}
+
+fn text_length(text: &str) -> usize {
+    let mut length = 0;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let mut emoji = is_pictographic(c);
+        // Two regional indicators make one flag
+        if is_regional_indicator(c) && chars.peek().copied().is_some_and(is_regional_indicator) {
+            chars.next();
+        }
+        // Fold whatever extends this character into it
+        loop {
+            match chars.peek().copied() {
+                Some(m) if is_modifier(m) => {
+                    emoji |= m == '\u{FE0F}' || m == '\u{20E3}';
+                    chars.next();
+                }
+                Some('\u{200D}') if emoji => {
+                    chars.next();
+                    chars.next();
+                }
+                _ => break,
+            }
+        }
+        length += if emoji || !is_light(c) { 2 } else { 1 };
+    }
+    length
+}
+
+// The ranges that count as 1
+fn is_light(c: char) -> bool {
+    matches!(
+        c as u32,
+        0..=0x10FF | 0x2000..=0x200D | 0x2010..=0x201F | 0x2032..=0x2037
+    )
+}
+
+fn is_pictographic(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x2300..=0x23FF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0x1F000..=0x1FAFF
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c as u32, 0x1F1E6..=0x1F1FF)
+}
+
+// Variation selectors, the keycap mark, skin tones and flag tags
+fn is_modifier(c: char) -> bool {
+    matches!(
+        c as u32,
+        0xFE0E | 0xFE0F | 0x20E3 | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Tweet, TweetError, TweetType};
+
+    #[test]
+    fn weighs_scripts() {
+        assert_eq!(weighted_length(""), 0);
+        assert_eq!(weighted_length("Straße, naïve café"), 18);
+        assert_eq!(weighted_length("Привет"), 6);
+        assert_eq!(weighted_length("“quoted” — dash"), 15);
+        assert_eq!(weighted_length("日本語のツイート"), 16);
+        assert_eq!(weighted_length("한국어"), 6);
+    }
+
+    #[test]
+    fn counts_each_emoji_once() {
+        assert_eq!(weighted_length("😀"), 2);
+        assert_eq!(weighted_length("👋🏿"), 2);
+        assert_eq!(weighted_length("👨‍👩‍👧‍👦"), 2);
+        assert_eq!(weighted_length("🇫🇷🇯🇵"), 4);
+        assert_eq!(weighted_length("1️⃣"), 2);
+        assert_eq!(weighted_length("❤️ ok"), 5);
+        assert_eq!(weighted_length("🏴󠁧󠁢󠁳󠁣󠁴󠁿"), 2);
+    }
+
+    #[test]
+    fn counts_links_as_fixed_length() {
+        assert_eq!(
+            find_urls("see https://a.example/x, or HTTP://b.example."),
+            [4..23, 28..44]
+        );
+        assert!(find_urls("nohttps://a.example https:// http").is_empty());
+        assert_eq!(weighted_length("https://t.co"), URL_LENGTH);
+        let long = format!("read https://example.com/{}", "a".repeat(500));
+        assert_eq!(weighted_length(&long), 5 + URL_LENGTH);
+    }
+
+    #[test]
+    fn tweets_use_weighted_length() {
+        let cjk = "字".repeat(140);
+        assert!(Tweet::new("a", &cjk, TweetType::Tweet, None).is_ok());
+        assert_eq!(
+            Tweet::new("a", &format!("{cjk}!"), TweetType::Tweet, None).err(),
+            Some(TweetError::TooLong { length: 281 })
+        );
+
+        let emoji = "🙂".repeat(140);
+        assert!(Tweet::new("a", &emoji, TweetType::Tweet, None).is_ok());
+
+        let links = format!(
+            "{} https://example.com/{}",
+            "a".repeat(256),
+            "b".repeat(300)
+        );
+        assert!(Tweet::new("a", &links, TweetType::Tweet, None).is_ok());
+
+        assert_eq!(
+            Tweet::new("a", "hi", TweetType::Reply, None).err(),
+            Some(TweetError::ReplyToNothing)
+        );
+    }
+}
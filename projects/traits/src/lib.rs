@@ -5,11 +5,35 @@ display summaries of text by calling a "summarize" method on an instance. We
 use this example to showcase traits.
 */
 
+pub mod content;
+pub mod digest;
+pub mod entities;
+pub mod feed;
+pub mod json;
+pub mod length;
+pub mod moderation;
+pub mod notifier;
+pub mod search;
+pub mod similar;
+pub mod summarizer;
+pub mod syndication;
+pub mod template;
+pub mod timeline;
+mod xml;
+
+use content::Metadata;
+use notifier::Notifier;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use template::SummaryOptions;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NewsArticle {
     headline: String,
     location: String,
-    author: String,
     content: String,
+    meta: Metadata,
 }
 
 impl NewsArticle {
@@ -17,29 +41,82 @@ impl NewsArticle {
         Self {
             headline: headline.to_string(),
             location: location.to_string(),
-            author: author.to_string(),
             content: content.to_string(),
+            meta: Metadata::new(author),
         }
     }
+
+    pub fn headline(&self) -> &str {
+        &self.headline
+    }
+
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
 }
 
 impl Summary for NewsArticle {
 	fn summarize(&self) -> String {
-		format!("{}, by {} ({})", self.headline, self.author, self.location)
+		format!("{}, by {} ({})", self.headline, self.meta.author, self.location)
+	}
+
+	fn author(&self) -> Option<&str> {
+		Some(&self.meta.author)
+	}
+
+	fn field(&self, name: &str) -> Option<String> {
+		match name {
+			"headline" => Some(self.headline.clone()),
+			"location" => Some(self.location.clone()),
+			"content" => Some(self.content.clone()),
+			_ => None,
+		}
+	}
+
+	fn default_template(&self) -> &'static str {
+		"{headline}[, by {author}][ ({location})]"
 	}
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TweetType {
     Tweet,
     Retweet,
     Reply,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TweetError {
+    /// Longer than length::MAX_LENGTH, counting the way length does.
+    TooLong { length: usize },
+    ReplyToNothing,
+}
+
+impl fmt::Display for TweetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TweetError::TooLong { length } => write!(
+                f,
+                "Tweet too long ({length} characters, the limit is {}).",
+                length::MAX_LENGTH
+            ),
+            TweetError::ReplyToNothing => write!(f, "Tweet is a reply to nothing."),
+        }
+    }
+}
+
+impl Error for TweetError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Tweet {
-    username: String,
     content: String,
     tweet_type: TweetType,
     replied_to: Option<u32>, // uid of tweet being replied to
+    meta: Metadata, // the author is the username
 }
 
 impl Tweet {
@@ -48,27 +125,62 @@ impl Tweet {
         content: &str,
         tweet_type: TweetType,
         replied_to: Option<u32>,
-    ) -> Result<Self, String> {
-        if content.len() > 280 {
-            return Err(String::from("Tweet too long."));
+    ) -> Result<Self, TweetError> {
+        let length = length::weighted_length(content);
+        if length > length::MAX_LENGTH {
+            return Err(TweetError::TooLong { length });
         } else if let TweetType::Reply = tweet_type {
             if replied_to.is_none() {
-                return Err(String::from("Tweet is a reply to nothing."));
+                return Err(TweetError::ReplyToNothing);
             }
         }
 
         Ok(Self {
-            username: username.to_string(),
             content: content.to_string(),
             tweet_type,
             replied_to,
+            meta: Metadata::new(username),
         })
     }
+
+    pub fn username(&self) -> &str {
+        &self.meta.author
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn tweet_type(&self) -> TweetType {
+        self.tweet_type
+    }
+
+    pub fn replied_to(&self) -> Option<u32> {
+        self.replied_to
+    }
 }
 
 impl Summary for Tweet {
 	fn summarize(&self) -> String {
-		format!("{}: {}", self.username, self.content)
+		format!("{}: {}", self.meta.author, self.content)
+	}
+
+	fn author(&self) -> Option<&str> {
+		Some(&self.meta.author)
+	}
+
+	fn field(&self, name: &str) -> Option<String> {
+		match name {
+			"username" => Some(self.meta.author.clone()),
+			"content" => Some(self.content.clone()),
+			"type" => Some(format!("{:?}", self.tweet_type).to_lowercase()),
+			"replied_to" => self.replied_to.map(|id| id.to_string()),
+			_ => None,
+		}
+	}
+
+	fn default_template(&self) -> &'static str {
+		"[{author}: ]{content}"
 	}
 }
 
@@ -77,10 +189,50 @@ pub trait Summary {
 	fn summarize(&self) -> String {
 		String::from("(Read more...)")
 	}
+
+	// Who wrote the item, for anything that groups or filters by it. Most
+	// items have one, but not all do.
+	fn author(&self) -> Option<&str> {
+		None
+	}
+
+	// Named values a template can use, besides summary and author, which
+	// every item has. None if the item doesn't have that field.
+	fn field(&self, _name: &str) -> Option<String> {
+		None
+	}
+
+	// What render uses when the options don't give a template. With every
+	// field filled in it should match summarize.
+	fn default_template(&self) -> &'static str {
+		"{summary}"
+	}
+
+	// The sentences of the item's content that best sum it up, at most
+	// count of them. Items without content fall back to summarize.
+	fn excerpt(&self, count: usize) -> String {
+		match self.field("content") {
+			Some(content) if !content.trim().is_empty() => summarizer::extract(&content, count),
+			_ => self.summarize(),
+		}
+	}
+
+	// The summary laid out by options. See the template module.
+	fn render(&self, options: &SummaryOptions) -> String {
+		template::render(self, options)
+	}
 }
 
 // A function that takes a trait as a parameter. Only types that implement the
 // trait can call this function.
 pub fn notify(item: &impl Summary) {
-	println!("Breaking news! {}", item.summarize());
+	notify_with(item, &mut notifier::Stdout).expect("failed printing to stdout");
+}
+
+// The same, through any channel. See the notifier module for routing items
+// to several. Notifiers take single lines, so line breaks in the summary
+// become spaces.
+pub fn notify_with(item: &(impl Summary + ?Sized), notifier: &mut dyn Notifier) -> io::Result<()> {
+	let summary = item.summarize().split_whitespace().collect::<Vec<_>>().join(" ");
+	notifier.send(&format!("Breaking news! {summary}"))
 }
@@ -0,0 +1,684 @@
+//! Moderation before anything reaches notify. Items go through a Pipeline
+//! of Filters in order. Each filter can let an item pass, flag it for a
+//! person to look at, rewrite its text, or drop it, which ends the chain.
+//! Every decision goes in the pipeline's audit log.
+
+use crate::content::Content;
+use crate::length::{find_urls, weighted_length, MAX_LENGTH};
+use crate::{NewsArticle, Tweet, TweetError};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::time::{Duration, SystemTime};
+
+/// Content that filters can read and rewrite.
+pub trait Moderate: Content {
+    /// Everything a reader would see, for filters to check. Separate
+    /// pieces of text, like a headline and its story, are separate
+    /// paragraphs.
+    fn text(&self) -> String;
+
+    /// Run every piece of the item's text through rewrite. If the result
+    /// isn't valid for the item, it's left as it was and the error says why.
+    fn rewrite_text(&mut self, rewrite: &dyn Fn(&str) -> String) -> Result<(), String>;
+}
+
+impl Moderate for NewsArticle {
+    fn text(&self) -> String {
+        format!("{}\n\n{}", self.headline, self.content)
+    }
+
+    fn rewrite_text(&mut self, rewrite: &dyn Fn(&str) -> String) -> Result<(), String> {
+        self.headline = rewrite(&self.headline);
+        self.content = rewrite(&self.content);
+        Ok(())
+    }
+}
+
+impl Moderate for Tweet {
+    fn text(&self) -> String {
+        self.content.clone()
+    }
+
+    // The same length check as Tweet::new, so a rewrite can't make a
+    // tweet that couldn't have been posted
+    fn rewrite_text(&mut self, rewrite: &dyn Fn(&str) -> String) -> Result<(), String> {
+        let content = rewrite(&self.content);
+        let length = weighted_length(&content);
+        if length > MAX_LENGTH {
+            return Err(TweetError::TooLong { length }.to_string());
+        }
+        self.content = content;
+        Ok(())
+    }
+}
+
+/// What a filter decided about an item. The reasons go in the audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Flag(String),
+    /// The filter has already changed the item's text.
+    Rewrite(String),
+    Drop(String),
+}
+
+pub trait Filter {
+    /// Identifies the filter in the audit log.
+    fn name(&self) -> &str;
+
+    /// Decide about item, which arrived at the given time.
+    fn check(&mut self, item: &mut dyn Moderate, at: SystemTime) -> Verdict;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// No filter had anything to say.
+    Pass,
+    Flag,
+    Rewrite,
+    Drop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub at: SystemTime,
+    pub author: String,
+    /// The item's summary when the decision was made.
+    pub summary: String,
+    /// Empty for Pass.
+    pub filter: String,
+    pub action: Action,
+    pub reason: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome<T> {
+    /// Through every filter, perhaps rewritten, with nothing flagged.
+    Passed(T),
+    /// Through every filter, but with reasons for someone to look at it.
+    Flagged {
+        item: T,
+        reasons: Vec<String>,
+    },
+    Dropped {
+        reason: String,
+    },
+}
+
+impl<T> Outcome<T> {
+    /// The item, unless it was dropped.
+    pub fn into_item(self) -> Option<T> {
+        match self {
+            Outcome::Passed(item) | Outcome::Flagged { item, .. } => Some(item),
+            Outcome::Dropped { .. } => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    filters: Vec<Box<dyn Filter>>,
+    log: Vec<AuditEntry>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a filter after those already there.
+    pub fn with(mut self, filter: impl Filter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    pub fn moderate<T: Moderate>(&mut self, mut item: T, at: SystemTime) -> Outcome<T> {
+        let mut reasons = Vec::new();
+        let mut touched = false;
+
+        for filter in &mut self.filters {
+            let (action, reason) = match filter.check(&mut item, at) {
+                Verdict::Pass => continue,
+                Verdict::Flag(reason) => (Action::Flag, reason),
+                Verdict::Rewrite(reason) => (Action::Rewrite, reason),
+                Verdict::Drop(reason) => (Action::Drop, reason),
+            };
+            touched = true;
+            self.log.push(AuditEntry {
+                at,
+                author: item.metadata().author.clone(),
+                summary: item.summarize(),
+                filter: filter.name().to_string(),
+                action,
+                reason: reason.clone(),
+            });
+            match action {
+                Action::Drop => return Outcome::Dropped { reason },
+                Action::Flag => reasons.push(reason),
+                _ => (),
+            }
+        }
+
+        if !touched {
+            self.log.push(AuditEntry {
+                at,
+                author: item.metadata().author.clone(),
+                summary: item.summarize(),
+                filter: String::new(),
+                action: Action::Pass,
+                reason: String::new(),
+            });
+        }
+        if reasons.is_empty() {
+            Outcome::Passed(item)
+        } else {
+            Outcome::Flagged { item, reasons }
+        }
+    }
+
+    /// Every decision so far, oldest first.
+    pub fn log(&self) -> &[AuditEntry] {
+        &self.log
+    }
+
+    /// Hand over the log and start a new one.
+    pub fn take_log(&mut self) -> Vec<AuditEntry> {
+        std::mem::take(&mut self.log)
+    }
+}
+
+/// Words and phrases that aren't allowed, matched whole and ignoring case.
+/// A phrase like "bad word" or "don't" matches its words with spaces,
+/// hyphens or apostrophes between them, but not across paragraphs.
+pub struct BannedWords {
+    phrases: Vec<Vec<String>>,
+    drop: bool,
+}
+
+impl BannedWords {
+    /// Drops items using any of words.
+    pub fn dropping(words: &[&str]) -> Self {
+        Self::new(words, true)
+    }
+
+    /// Lets items through with the words starred out.
+    pub fn masking(words: &[&str]) -> Self {
+        Self::new(words, false)
+    }
+
+    // Entries without any letters or digits can't match anything
+    fn new(words: &[&str], drop: bool) -> Self {
+        Self {
+            phrases: words
+                .iter()
+                .map(|word| words_in(word).map(|(_, word)| word).collect())
+                .filter(|phrase: &Vec<String>| !phrase.is_empty())
+                .collect(),
+            drop,
+        }
+    }
+
+    // Byte ranges of the banned words and phrases in text
+    fn find(&self, text: &str) -> Vec<(usize, usize)> {
+        let words: Vec<(Range<usize>, String)> = words_in(text).collect();
+        let mut found = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let longest = self
+                .phrases
+                .iter()
+                .filter(|phrase| {
+                    words.len() - i >= phrase.len()
+                        && phrase.iter().zip(&words[i..]).all(|(a, (_, b))| a == b)
+                        && words[i..i + phrase.len()]
+                            .windows(2)
+                            .all(|pair| joins(&text[pair[0].0.end..pair[1].0.start]))
+                })
+                .map(Vec::len)
+                .max();
+            match longest {
+                Some(length) => {
+                    found.push((words[i].0.start, words[i + length - 1].0.end));
+                    i += length;
+                }
+                None => i += 1,
+            }
+        }
+        found
+    }
+
+    // Only the letters and digits are starred, so "bad-word" is "***-****"
+    fn mask(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut copied = 0;
+        for (from, to) in self.find(text) {
+            out.push_str(&text[copied..from]);
+            out.extend(
+                text[from..to]
+                    .chars()
+                    .map(|c| if c.is_alphanumeric() { '*' } else { c }),
+            );
+            copied = to;
+        }
+        out.push_str(&text[copied..]);
+        out
+    }
+}
+
+// Whether what's between two words keeps them in one phrase
+fn joins(gap: &str) -> bool {
+    gap.chars()
+        .all(|c| c.is_whitespace() || matches!(c, '-' | '\'' | '’'))
+        && gap.matches('\n').count() <= 1
+}
+
+// Runs of letters and digits in text, lowercased, with where they are
+fn words_in(text: &str) -> impl Iterator<Item = (Range<usize>, String)> + '_ {
+    let mut start = None;
+    text.char_indices()
+        .chain([(text.len(), ' ')])
+        .filter_map(move |(at, c)| match (start, c.is_alphanumeric()) {
+            (None, true) => {
+                start = Some(at);
+                None
+            }
+            (Some(from), false) => {
+                start = None;
+                Some((from..at, text[from..at].to_lowercase()))
+            }
+            _ => None,
+        })
+}
+
+impl Filter for BannedWords {
+    fn name(&self) -> &str {
+        "banned words"
+    }
+
+    fn check(&mut self, item: &mut dyn Moderate, _: SystemTime) -> Verdict {
+        let text = item.text();
+        let Some(&(from, to)) = self.find(&text).first() else {
+            return Verdict::Pass;
+        };
+        let reason = format!("uses \"{}\"", &text[from..to]);
+        if self.drop {
+            Verdict::Drop(reason)
+        } else {
+            match item.rewrite_text(&|text| self.mask(text)) {
+                Ok(()) => Verdict::Rewrite(reason),
+                Err(error) => Verdict::Drop(format!("{reason}, and masking it failed: {error}")),
+            }
+        }
+    }
+}
+
+/// Drops everything by the muted authors.
+#[derive(Default)]
+pub struct MuteList {
+    authors: HashSet<String>,
+}
+
+impl MuteList {
+    pub fn new(authors: &[&str]) -> Self {
+        Self {
+            authors: authors.iter().map(|author| author.to_string()).collect(),
+        }
+    }
+
+    pub fn mute(&mut self, author: &str) {
+        self.authors.insert(author.to_string());
+    }
+
+    pub fn unmute(&mut self, author: &str) {
+        self.authors.remove(author);
+    }
+}
+
+impl Filter for MuteList {
+    fn name(&self) -> &str {
+        "mute list"
+    }
+
+    fn check(&mut self, item: &mut dyn Moderate, _: SystemTime) -> Verdict {
+        let author = &item.metadata().author;
+        if self.authors.contains(author) {
+            Verdict::Drop(format!("{author} is muted"))
+        } else {
+            Verdict::Pass
+        }
+    }
+}
+
+/// Signs of spam. The same link twice is dropped, and more than max_links
+/// links, or shouting in capitals, gets flagged.
+pub struct Spam {
+    pub max_links: usize,
+}
+
+impl Default for Spam {
+    fn default() -> Self {
+        Self { max_links: 2 }
+    }
+}
+
+// Shorter text in capitals is more likely an acronym than shouting
+const SHOUTING_LETTERS: usize = 20;
+
+impl Filter for Spam {
+    fn name(&self) -> &str {
+        "spam"
+    }
+
+    fn check(&mut self, item: &mut dyn Moderate, _: SystemTime) -> Verdict {
+        let text = item.text();
+        let links: Vec<String> = find_urls(&text)
+            .into_iter()
+            .map(|range| text[range].to_lowercase())
+            .collect();
+        let unique: HashSet<&String> = links.iter().collect();
+        if unique.len() < links.len() {
+            return Verdict::Drop(String::from("repeats a link"));
+        }
+        if links.len() > self.max_links {
+            return Verdict::Flag(format!("has {} links", links.len()));
+        }
+
+        let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+        let capitals = letters.iter().filter(|c| c.is_uppercase()).count();
+        if letters.len() >= SHOUTING_LETTERS && capitals * 5 > letters.len() * 4 {
+            return Verdict::Flag(String::from("shouting"));
+        }
+        Verdict::Pass
+    }
+}
+
+/// Drops items from an author who has already had max items in the last
+/// window.
+pub struct RateLimit {
+    max: usize,
+    window: Duration,
+    recent: HashMap<String, VecDeque<SystemTime>>,
+    swept: Option<SystemTime>,
+}
+
+impl RateLimit {
+    pub fn new(max: usize, window: Duration) -> Self {
+        Self {
+            max,
+            window,
+            recent: HashMap::new(),
+            swept: None,
+        }
+    }
+
+    fn expired(&self, time: SystemTime, at: SystemTime) -> bool {
+        at.duration_since(time).unwrap_or_default() >= self.window
+    }
+
+    // Forget authors with nothing left in the window, once a window, so
+    // everyone who ever posted isn't remembered forever
+    fn sweep(&mut self, at: SystemTime) {
+        if self.swept.is_some_and(|swept| !self.expired(swept, at)) {
+            return;
+        }
+        let mut recent = std::mem::take(&mut self.recent);
+        recent.retain(|_, times| times.back().is_some_and(|&time| !self.expired(time, at)));
+        self.recent = recent;
+        self.swept = Some(at);
+    }
+}
+
+impl Filter for RateLimit {
+    fn name(&self) -> &str {
+        "rate limit"
+    }
+
+    fn check(&mut self, item: &mut dyn Moderate, at: SystemTime) -> Verdict {
+        self.sweep(at);
+        let author = &item.metadata().author;
+        let mut recent = self.recent.remove(author).unwrap_or_default();
+        while recent.front().is_some_and(|&time| self.expired(time, at)) {
+            recent.pop_front();
+        }
+        let verdict = if recent.len() >= self.max {
+            Verdict::Drop(format!(
+                "{author} posted {} times in {}s",
+                recent.len(),
+                self.window.as_secs()
+            ))
+        } else {
+            recent.push_back(at);
+            Verdict::Pass
+        };
+        if !recent.is_empty() {
+            self.recent.insert(author.clone(), recent);
+        }
+        verdict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Summary, TweetType};
+    use std::time::UNIX_EPOCH;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn tweet(username: &str, content: &str) -> Tweet {
+        Tweet::new(username, content, TweetType::Tweet, None).unwrap()
+    }
+
+    #[test]
+    fn drops_and_masks_banned_words() {
+        let mut dropping = Pipeline::new().with(BannedWords::dropping(&["Heck"]));
+        assert_eq!(
+            dropping.moderate(tweet("a", "what the HECK"), at(0)),
+            Outcome::Dropped {
+                reason: String::from("uses \"HECK\"")
+            }
+        );
+        assert!(matches!(
+            dropping.moderate(tweet("a", "checking heckles"), at(0)),
+            Outcome::Passed(_)
+        ));
+
+        let mut masking = Pipeline::new().with(BannedWords::masking(&["heck", "darn"]));
+        let article = NewsArticle::new("Heck of a game", "Pittsburgh", "a", "Darn close, heck.");
+        let article = masking.moderate(article, at(0)).into_item().unwrap();
+        assert_eq!(article.headline(), "**** of a game");
+        assert_eq!(article.content(), "**** close, ****.");
+        assert_eq!(masking.log()[0].action, Action::Rewrite);
+    }
+
+    #[test]
+    fn matches_banned_phrases() {
+        let mut dropping =
+            Pipeline::new().with(BannedWords::dropping(&["bad word", "don't", "!!"]));
+        for text in ["a BAD  word", "bad-word here", "I DON'T", "don’t"] {
+            assert!(
+                matches!(
+                    dropping.moderate(tweet("a", text), at(0)),
+                    Outcome::Dropped { .. }
+                ),
+                "{text}"
+            );
+        }
+        for text in ["bad, word", "bad words", "badword", "donut", "!!"] {
+            assert!(
+                matches!(
+                    dropping.moderate(tweet("a", text), at(0)),
+                    Outcome::Passed(_)
+                ),
+                "{text}"
+            );
+        }
+
+        let mut masking = Pipeline::new().with(BannedWords::masking(&["heck", "heck no"]));
+        let tweet = masking
+            .moderate(tweet("a", "Heck-no, heck."), at(0))
+            .into_item();
+        assert_eq!(tweet.unwrap().content(), "****-**, ****.");
+
+        let article = NewsArticle::new("So bad", "Pittsburgh", "a", "Word up\nbad\nword");
+        let mut masking = Pipeline::new().with(BannedWords::masking(&["bad word"]));
+        let article = masking.moderate(article, at(0)).into_item().unwrap();
+        assert_eq!(article.headline(), "So bad");
+        assert_eq!(article.content(), "Word up\n***\n****");
+        let article = NewsArticle::new("So bad", "Pittsburgh", "a", "Word up");
+        assert!(matches!(
+            dropping.moderate(article, at(0)),
+            Outcome::Passed(_)
+        ));
+    }
+
+    #[test]
+    fn rewrites_keep_tweets_valid() {
+        struct Shout;
+
+        impl Filter for Shout {
+            fn name(&self) -> &str {
+                "shout"
+            }
+
+            fn check(&mut self, item: &mut dyn Moderate, _: SystemTime) -> Verdict {
+                match item.rewrite_text(&|text| text.repeat(3)) {
+                    Ok(()) => Verdict::Rewrite(String::from("louder")),
+                    Err(error) => Verdict::Drop(error),
+                }
+            }
+        }
+
+        let mut pipeline = Pipeline::new().with(Shout);
+        let short = pipeline.moderate(tweet("a", "go "), at(0)).into_item();
+        assert_eq!(short.unwrap().content(), "go go go ");
+
+        let mut long = tweet("a", &"a".repeat(100));
+        assert!(long.rewrite_text(&|text| text.repeat(3)).is_err());
+        assert_eq!(long.content(), "a".repeat(100));
+        assert_eq!(
+            pipeline.moderate(long, at(0)),
+            Outcome::Dropped {
+                reason: TweetError::TooLong { length: 300 }.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn mutes_authors() {
+        let mut mutes = MuteList::new(&["troll"]);
+        mutes.mute("spammer");
+        mutes.unmute("troll");
+        let mut pipeline = Pipeline::new().with(mutes);
+
+        assert!(pipeline
+            .moderate(tweet("spammer", "hi"), at(0))
+            .into_item()
+            .is_none());
+        assert!(pipeline
+            .moderate(tweet("troll", "hi"), at(0))
+            .into_item()
+            .is_some());
+    }
+
+    #[test]
+    fn spots_spam() {
+        let mut pipeline = Pipeline::new().with(Spam::default());
+        let repeated = "deals https://x.example and https://X.example!";
+        assert_eq!(
+            pipeline.moderate(tweet("a", repeated), at(0)),
+            Outcome::Dropped {
+                reason: String::from("repeats a link")
+            }
+        );
+
+        let many = "https://a.example https://b.example https://c.example";
+        let Outcome::Flagged { reasons, .. } = pipeline.moderate(tweet("a", many), at(0)) else {
+            panic!("not flagged");
+        };
+        assert_eq!(reasons, ["has 3 links"]);
+
+        let shouting = tweet("a", "THIS IS THE BEST DEAL YOU WILL EVER SEE");
+        assert!(matches!(
+            pipeline.moderate(shouting, at(0)),
+            Outcome::Flagged { .. }
+        ));
+        assert!(matches!(
+            pipeline.moderate(tweet("a", "NASA and the ESA"), at(0)),
+            Outcome::Passed(_)
+        ));
+    }
+
+    #[test]
+    fn limits_rates_per_author() {
+        let mut pipeline = Pipeline::new().with(RateLimit::new(2, Duration::from_secs(60)));
+        let mut passed = |username, secs| {
+            pipeline
+                .moderate(tweet(username, "hi"), at(secs))
+                .into_item()
+                .is_some()
+        };
+        assert!(passed("a", 0));
+        assert!(passed("a", 10));
+        assert!(!passed("a", 20));
+        assert!(passed("b", 20));
+        assert!(passed("a", 60));
+        assert!(!passed("a", 65));
+        assert!(passed("a", 71));
+    }
+
+    #[test]
+    fn forgets_quiet_authors() {
+        let mut limit = RateLimit::new(1, Duration::from_secs(60));
+        for (secs, username) in [(0, "a"), (10, "b"), (20, "c")] {
+            limit.check(&mut tweet(username, "hi"), at(secs));
+        }
+        assert_eq!(limit.recent.len(), 3);
+        limit.check(&mut tweet("c", "hi"), at(75));
+        assert_eq!(limit.recent.keys().collect::<Vec<_>>(), ["c"]);
+        limit.check(&mut tweet("d", "hi"), at(200));
+        assert_eq!(limit.recent.keys().collect::<Vec<_>>(), ["d"]);
+
+        let mut closed = RateLimit::new(0, Duration::from_secs(60));
+        closed.check(&mut tweet("a", "hi"), at(0));
+        assert!(closed.recent.is_empty());
+    }
+
+    #[test]
+    fn logs_every_decision() {
+        let mut pipeline = Pipeline::new()
+            .with(MuteList::new(&["troll"]))
+            .with(BannedWords::masking(&["heck"]))
+            .with(Spam::default());
+
+        pipeline.moderate(tweet("a", "nice game"), at(1));
+        pipeline.moderate(tweet("troll", "heck"), at(2));
+        let outcome = pipeline.moderate(tweet("b", "HECK YES WHAT A GAME THAT WAS TONIGHT"), at(3));
+        let Outcome::Flagged { item, reasons } = outcome else {
+            panic!("not flagged");
+        };
+        assert_eq!(item.content(), "**** YES WHAT A GAME THAT WAS TONIGHT");
+        assert_eq!(reasons, ["shouting"]);
+
+        let log = pipeline.take_log();
+        let actions: Vec<(Action, &str)> = log
+            .iter()
+            .map(|entry| (entry.action, entry.filter.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (Action::Pass, ""),
+                (Action::Drop, "mute list"),
+                (Action::Rewrite, "banned words"),
+                (Action::Flag, "spam"),
+            ]
+        );
+        assert_eq!(log[1].author, "troll");
+        assert_eq!(log[1].summary, tweet("troll", "heck").summarize());
+        assert_eq!(log[3].summary, item.summarize());
+        assert!(pipeline.log().is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+//! Where notify sends its messages. A Notifier delivers one message to one
+//! channel. A Router holds several, each behind a Rule picking which items
+//! it gets, such as only the replies from certain users.
+
+use crate::Summary;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+pub trait Notifier {
+    /// Deliver one message, which is a single line without the newline.
+    fn send(&mut self, message: &str) -> io::Result<()>;
+}
+
+pub struct Stdout;
+
+impl Notifier for Stdout {
+    fn send(&mut self, message: &str) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        writeln!(out, "{message}")?;
+        out.flush()
+    }
+}
+
+/// Appends each message as a line to a file, creating it if needed.
+pub struct FileAppender {
+    file: File,
+}
+
+impl FileAppender {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+}
+
+impl Notifier for FileAppender {
+    fn send(&mut self, message: &str) -> io::Result<()> {
+        // One write per line, so lines from several appenders don't interleave
+        self.file.write_all(format!("{message}\n").as_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// Writes each message as a line to a listening Unix socket. It connects
+/// per message, so the listener can restart between them.
+#[cfg(unix)]
+pub struct UnixSocket {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocket {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Notifier for UnixSocket {
+    fn send(&mut self, message: &str) -> io::Result<()> {
+        let mut stream = std::os::unix::net::UnixStream::connect(&self.path)?;
+        stream.write_all(format!("{message}\n").as_bytes())
+    }
+}
+
+/// Keeps every message in memory, for tests. Clones share what they've
+/// recorded, so keep one to look at after handing the other to a Router.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    messages: Rc<RefCell<Vec<String>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.borrow().clone()
+    }
+}
+
+impl Notifier for Recorder {
+    fn send(&mut self, message: &str) -> io::Result<()> {
+        self.messages.borrow_mut().push(message.to_string());
+        Ok(())
+    }
+}
+
+type Condition = Box<dyn Fn(&dyn Summary) -> bool>;
+
+/// Which items a route takes. Every condition added has to hold, and a rule
+/// with none takes everything.
+#[derive(Default)]
+pub struct Rule {
+    conditions: Vec<Condition>,
+}
+
+impl Rule {
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Only items by one of authors.
+    pub fn from_authors(self, authors: &[&str]) -> Self {
+        let authors: Vec<String> = authors.iter().map(|author| author.to_string()).collect();
+        self.matching(move |item| {
+            item.author()
+                .is_some_and(|author| authors.iter().any(|allowed| allowed == author))
+        })
+    }
+
+    /// Only items whose field name is value, such as type "reply".
+    pub fn with_field(self, name: &str, value: &str) -> Self {
+        let (name, value) = (name.to_string(), value.to_string());
+        self.matching(move |item| item.field(&name).as_deref() == Some(value.as_str()))
+    }
+
+    pub fn matching(mut self, condition: impl Fn(&dyn Summary) -> bool + 'static) -> Self {
+        self.conditions.push(Box::new(condition));
+        self
+    }
+
+    pub fn matches(&self, item: &dyn Summary) -> bool {
+        self.conditions.iter().all(|condition| condition(item))
+    }
+}
+
+/// The routes that failed to deliver an item.
+#[derive(Debug)]
+pub struct NotifyError {
+    pub delivered: usize,
+    pub failed: Vec<(String, io::Error)>,
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "couldn't notify ")?;
+        for (i, (route, error)) in self.failed.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{route} ({error})")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for NotifyError {}
+
+struct Route {
+    name: String,
+    rule: Rule,
+    notifier: Box<dyn Notifier>,
+}
+
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send items matching rule to notifier. name identifies the route in
+    /// errors.
+    pub fn route(&mut self, name: &str, rule: Rule, notifier: impl Notifier + 'static) {
+        self.routes.push(Route {
+            name: name.to_string(),
+            rule,
+            notifier: Box::new(notifier),
+        });
+    }
+
+    /// Notify every route whose rule matches item, and return how many
+    /// did. One route failing doesn't stop the rest from being tried.
+    pub fn dispatch(&mut self, item: &dyn Summary) -> Result<usize, NotifyError> {
+        let mut delivered = 0;
+        let mut failed = Vec::new();
+
+        for route in &mut self.routes {
+            if !route.rule.matches(item) {
+                continue;
+            }
+            match crate::notify_with(item, route.notifier.as_mut()) {
+                Ok(()) => delivered += 1,
+                Err(error) => failed.push((route.name.clone(), error)),
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(delivered)
+        } else {
+            Err(NotifyError { delivered, failed })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewsArticle, Tweet, TweetType};
+    use std::fs;
+
+    fn scratch(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("traits-{}-{name}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn reply(username: &str, content: &str) -> Tweet {
+        Tweet::new(username, content, TweetType::Reply, Some(1)).unwrap()
+    }
+
+    struct Broken;
+
+    impl Notifier for Broken {
+        fn send(&mut self, _: &str) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "gone"))
+        }
+    }
+
+    #[test]
+    fn routes_by_rule() {
+        let everything = Recorder::new();
+        let replies = Recorder::new();
+        let mut router = Router::new();
+        router.route("all", Rule::all(), everything.clone());
+        router.route(
+            "replies",
+            Rule::all()
+                .with_field("type", "reply")
+                .from_authors(&["dril", "wint"]),
+            replies.clone(),
+        );
+
+        let article = NewsArticle::new("Penguins win", "Pittsburgh", "dril", "");
+        assert_eq!(router.dispatch(&article).unwrap(), 1);
+        assert_eq!(router.dispatch(&reply("dril", "no")).unwrap(), 2);
+        assert_eq!(router.dispatch(&reply("horse_ebooks", "yes")).unwrap(), 1);
+
+        assert_eq!(everything.messages().len(), 3);
+        assert_eq!(replies.messages(), ["Breaking news! dril: no"]);
+
+        router.dispatch(&reply("wint", "one\n\ntwo\r\n three ")).unwrap();
+        assert_eq!(replies.messages()[1], "Breaking news! wint: one two three");
+    }
+
+    #[test]
+    fn reports_failed_routes() {
+        let recorder = Recorder::new();
+        let mut router = Router::new();
+        router.route("broken", Rule::all(), Broken);
+        router.route("recorder", Rule::all(), recorder.clone());
+
+        let error = router.dispatch(&reply("dril", "no")).unwrap_err();
+        assert_eq!(error.delivered, 1);
+        assert_eq!(error.failed.len(), 1);
+        assert_eq!(error.to_string(), "couldn't notify broken (gone)");
+        assert_eq!(recorder.messages().len(), 1);
+    }
+
+    #[test]
+    fn appends_to_files() {
+        let path = scratch("appender");
+        let mut appender = FileAppender::open(&path).unwrap();
+        appender.send("one").unwrap();
+        FileAppender::open(&path).unwrap().send("two").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        fs::remove_file(&path).unwrap();
+
+        let missing = std::env::temp_dir().join("no-such-dir").join("log");
+        assert!(FileAppender::open(&missing).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_to_unix_sockets() {
+        use std::io::Read;
+        use std::os::unix::net::UnixListener;
+
+        let path = scratch("socket");
+        let mut socket = UnixSocket::new(&path);
+        assert!(socket.send("nobody listening").is_err());
+
+        let listener = UnixListener::bind(&path).unwrap();
+        socket.send("hello").unwrap();
+        let mut received = String::new();
+        listener
+            .accept()
+            .unwrap()
+            .0
+            .read_to_string(&mut received)
+            .unwrap();
+        assert_eq!(received, "hello\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
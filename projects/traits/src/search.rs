@@ -0,0 +1,240 @@
+//! Keyword search over content, ranked with BM25. Articles are indexed by
+//! headline and content, with headline words counting double, and tweets
+//! by their content. Anything else is indexed by its summary.
+
+use crate::Summary;
+use std::collections::HashMap;
+
+// The usual BM25 settings: how quickly repeats of a word stop adding to the
+// score, and how much a long document is marked down for its length
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+const HEADLINE_WEIGHT: u32 = 2;
+
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "are", "as", "at", "be", "been", "but",
+    "by", "can", "for", "from", "has", "have", "he", "her", "his", "i", "if", "in", "into", "is",
+    "it", "its", "just", "not", "of", "on", "or", "our", "she", "so", "than", "that", "the",
+    "their", "them", "then", "there", "they", "this", "to", "was", "we", "were", "what", "when",
+    "which", "who", "will", "with", "you", "your",
+];
+
+/// Lowercased words of text, without stop words. Possessives and other
+/// apostrophes are dropped, so "Penguins'" and "penguins" match.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '’'))
+        .map(|word| {
+            let word = word.trim_matches(['\'', '’']).to_lowercase();
+            let word = word
+                .strip_suffix("'s")
+                .or_else(|| word.strip_suffix("’s"))
+                .unwrap_or(&word);
+            word.replace(['\'', '’'], "")
+        })
+        .filter(|word| !word.is_empty() && !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+struct Document {
+    item: Box<dyn Summary>,
+    // Words counted, with headline words weighted
+    length: u32,
+}
+
+/// One search result. id is what add returned for the item.
+pub struct Hit<'a> {
+    pub id: usize,
+    pub score: f64,
+    pub item: &'a dyn Summary,
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    documents: Vec<Document>,
+    // For each word, the documents that have it and how many times
+    postings: HashMap<String, Vec<(usize, u32)>>,
+    total_length: u64,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index item and return its id. Ids count up from 0.
+    pub fn add(&mut self, item: impl Summary + 'static) -> usize {
+        self.add_boxed(Box::new(item))
+    }
+
+    pub fn add_boxed(&mut self, item: Box<dyn Summary>) -> usize {
+        let id = self.documents.len();
+        let mut counts: HashMap<String, u32> = HashMap::new();
+
+        let headline = item.field("headline");
+        let content = item.field("content");
+        if headline.is_none() && content.is_none() {
+            for word in tokenize(&item.summarize()) {
+                *counts.entry(word).or_default() += 1;
+            }
+        }
+        for word in tokenize(headline.as_deref().unwrap_or("")) {
+            *counts.entry(word).or_default() += HEADLINE_WEIGHT;
+        }
+        for word in tokenize(content.as_deref().unwrap_or("")) {
+            *counts.entry(word).or_default() += 1;
+        }
+
+        let length = counts.values().sum();
+        for (word, count) in counts {
+            self.postings.entry(word).or_default().push((id, count));
+        }
+        self.total_length += u64::from(length);
+        self.documents.push(Document { item, length });
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&dyn Summary> {
+        self.documents
+            .get(id)
+            .map(|document| document.item.as_ref())
+    }
+
+    /// The best limit matches for query, best first. Items matching none of
+    /// its words aren't returned, and equal scores go to the item added
+    /// first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<Hit<'_>> {
+        let mut words = tokenize(query);
+        words.sort();
+        words.dedup();
+
+        let count = self.documents.len() as f64;
+        let average_length = self.total_length as f64 / count.max(1.0);
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+
+        for word in &words {
+            let Some(postings) = self.postings.get(word) else {
+                continue;
+            };
+            let having = postings.len() as f64;
+            let idf = (1.0 + (count - having + 0.5) / (having + 0.5)).ln();
+            for &(id, frequency) in postings {
+                let frequency = f64::from(frequency);
+                let length = f64::from(self.documents[id].length);
+                let norm = K1 * (1.0 - B + B * length / average_length.max(1.0));
+                *scores.entry(id).or_default() += idf * frequency * (K1 + 1.0) / (frequency + norm);
+            }
+        }
+
+        let mut hits: Vec<Hit<'_>> = scores
+            .into_iter()
+            .map(|(id, score)| Hit {
+                id,
+                score,
+                item: self.documents[id].item.as_ref(),
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewsArticle, Tweet, TweetType};
+
+    struct Untitled(&'static str);
+
+    impl Summary for Untitled {
+        fn summarize(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    fn index() -> SearchIndex {
+        let mut index = SearchIndex::new();
+        index.add(NewsArticle::new(
+            "Penguins win the Stanley Cup",
+            "Pittsburgh",
+            "Iceburgh",
+            "The Penguins beat the Predators in six games.",
+        ));
+        index.add(NewsArticle::new(
+            "Snow expected downtown",
+            "Pittsburgh",
+            "Al Roker",
+            "Fans heading to the Penguins' parade should dress warmly.",
+        ));
+        index.add(
+            Tweet::new(
+                "dril",
+                "the stanley cup is just a big bowl",
+                TweetType::Tweet,
+                None,
+            )
+            .unwrap(),
+        );
+        index.add(Untitled("A parade of penguins at the zoo"));
+        index
+    }
+
+    fn ids(hits: &[Hit<'_>]) -> Vec<usize> {
+        hits.iter().map(|hit| hit.id).collect()
+    }
+
+    #[test]
+    fn tokenizes() {
+        assert_eq!(
+            tokenize("The Penguins' win — it's Pittsburgh's 6th CUP!"),
+            ["penguins", "win", "pittsburgh", "6th", "cup"]
+        );
+        assert!(tokenize("the and of").is_empty());
+    }
+
+    #[test]
+    fn ranks_headlines_first() {
+        let index = index();
+        assert_eq!(index.len(), 4);
+
+        let hits = index.search("penguins", 10);
+        assert_eq!(ids(&hits), [0, 3, 1]);
+        assert!(hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert_eq!(
+            hits[0].item.summarize(),
+            "Penguins win the Stanley Cup, by Iceburgh (Pittsburgh)"
+        );
+
+        // A short tweet that's all about the cup beats a longer article
+        assert_eq!(ids(&index.search("Stanley Cup", 10)), [2, 0]);
+        assert_eq!(ids(&index.search("penguins parade", 2)), [3, 1]);
+    }
+
+    #[test]
+    fn rare_words_count_more() {
+        let index = index();
+        // Every Penguins story matches, but only one has the predators
+        let hits = index.search("penguins predators", 10);
+        assert_eq!(hits[0].id, 0);
+        assert!(hits[0].score > 2.0 * hits[1].score);
+    }
+
+    #[test]
+    fn finds_nothing_for_nothing() {
+        let index = index();
+        assert!(index.search("", 10).is_empty());
+        assert!(index.search("the of and", 10).is_empty());
+        assert!(index.search("hockey", 10).is_empty());
+        assert!(SearchIndex::new().search("penguins", 10).is_empty());
+        assert!(index.search("penguins", 0).is_empty());
+    }
+}
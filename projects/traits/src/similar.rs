@@ -0,0 +1,276 @@
+//! Near-duplicate detection, for the same story arriving from several
+//! sources in slightly different words. Each item's text is cut into
+//! shingles, runs of a few words in a row, and a MinHash signature
+//! estimates how many shingles two items share. Items similar enough are
+//! clustered, and one per cluster is picked as the canonical copy.
+//!
+//! Articles are compared by headline and content, and anything else by its
+//! summary. Stop words are left out, so small rewordings matter less.
+
+use crate::search::tokenize;
+use crate::Summary;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateOptions {
+    /// Words per shingle. Smaller catches looser paraphrases, and also
+    /// more unrelated stories that share phrases.
+    pub shingle_size: usize,
+    /// Hash functions in each signature. More is more accurate and slower.
+    pub hashes: usize,
+    /// Estimated share of shingles in common, from 0 to 1, above which two
+    /// items are the same story.
+    pub threshold: f64,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        Self {
+            shingle_size: 2,
+            hashes: 128,
+            threshold: 0.3,
+        }
+    }
+}
+
+/// A MinHash signature: for each hash function, the smallest hash of any
+/// of the item's shingles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    minimums: Vec<u64>,
+}
+
+impl Signature {
+    /// Estimated Jaccard similarity of the two items' shingles, the share
+    /// of hash functions whose minimums agree. Items with no words at all
+    /// are like nothing, each other included.
+    pub fn similarity(&self, other: &Signature) -> f64 {
+        let pairs = self.minimums.iter().zip(&other.minimums);
+        let total = pairs.len();
+        let same = pairs.filter(|(a, b)| a == b && **a != u64::MAX).count();
+        if total == 0 {
+            0.0
+        } else {
+            same as f64 / total as f64
+        }
+    }
+}
+
+/// Items that are the same story. Indexes are into what was clustered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    pub canonical: usize,
+    /// Every item in the cluster, canonical included, in order.
+    pub members: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    options: DuplicateOptions,
+}
+
+impl Detector {
+    pub fn new(options: DuplicateOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &DuplicateOptions {
+        &self.options
+    }
+
+    pub fn signature(&self, item: &(impl Summary + ?Sized)) -> Signature {
+        let words = tokenize(&text_of(item));
+        let size = self.options.shingle_size.max(1);
+        let mut minimums = vec![u64::MAX; self.options.hashes];
+
+        // Text shorter than one shingle is a single shingle of what there is
+        for shingle in words.windows(size.min(words.len()).max(1)) {
+            let base = fnv1a(&shingle.join(" "));
+            for (seed, minimum) in minimums.iter_mut().enumerate() {
+                let hash = mix(base ^ mix(seed as u64 + 1));
+                *minimum = (*minimum).min(hash);
+            }
+        }
+        Signature { minimums }
+    }
+
+    pub fn similarity(&self, a: &(impl Summary + ?Sized), b: &(impl Summary + ?Sized)) -> f64 {
+        self.signature(a).similarity(&self.signature(b))
+    }
+
+    /// Group items into stories. Every item ends up in exactly one
+    /// cluster, alone if nothing is like it, and clusters come in order of
+    /// their first member.
+    pub fn clusters<T: Summary>(&self, items: &[T]) -> Vec<Cluster> {
+        self.cluster_items(items.iter().map(|item| item as &dyn Summary))
+    }
+
+    /// The canonical item of each cluster, in their original order.
+    pub fn canonical<'a, T: Summary>(&self, items: &'a [T]) -> Vec<&'a T> {
+        let mut canonical: Vec<usize> = self
+            .clusters(items)
+            .iter()
+            .map(|cluster| cluster.canonical)
+            .collect();
+        canonical.sort_unstable();
+        canonical.into_iter().map(|index| &items[index]).collect()
+    }
+
+    pub(crate) fn cluster_items<'a>(
+        &self,
+        items: impl Iterator<Item = &'a dyn Summary>,
+    ) -> Vec<Cluster> {
+        // Text lengths are for breaking ties between canonicals
+        let (signatures, lengths): (Vec<Signature>, Vec<usize>) = items
+            .map(|item| (self.signature(item), text_of(item).len()))
+            .unzip();
+        let count = signatures.len();
+        let mut similarities = vec![vec![0.0; count]; count];
+        let mut parents: Vec<usize> = (0..count).collect();
+
+        // Every pair, which is fine for a day's news. Linking transitively
+        // means a chain of rewrites still ends up as one story.
+        for i in 0..count {
+            for j in i + 1..count {
+                let similarity = signatures[i].similarity(&signatures[j]);
+                similarities[i][j] = similarity;
+                similarities[j][i] = similarity;
+                if similarity >= self.options.threshold {
+                    let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        let mut clusters: Vec<Cluster> = Vec::new();
+        let mut cluster_of = vec![usize::MAX; count];
+        for i in 0..count {
+            let first = root(&mut parents, i);
+            if cluster_of[first] == usize::MAX {
+                cluster_of[first] = clusters.len();
+                clusters.push(Cluster {
+                    canonical: i,
+                    members: Vec::new(),
+                });
+            }
+            clusters[cluster_of[first]].members.push(i);
+        }
+
+        // The canonical copy is the one most like the rest of its story,
+        // then the longest, then the first
+        for cluster in &mut clusters {
+            let closeness =
+                |i: usize| -> f64 { cluster.members.iter().map(|&j| similarities[i][j]).sum() };
+            cluster.canonical = *cluster
+                .members
+                .iter()
+                .max_by(|&&a, &&b| {
+                    closeness(a)
+                        .total_cmp(&closeness(b))
+                        .then(lengths[a].cmp(&lengths[b]))
+                        .then(b.cmp(&a))
+                })
+                .unwrap();
+        }
+        clusters
+    }
+}
+
+fn text_of(item: &(impl Summary + ?Sized)) -> String {
+    match (item.field("headline"), item.field("content")) {
+        (None, None) => item.summarize(),
+        (headline, content) => format!(
+            "{} {}",
+            headline.unwrap_or_default(),
+            content.unwrap_or_default()
+        ),
+    }
+}
+
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// splitmix64's finalizer, to turn one hash into many independent-looking ones
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NewsArticle;
+
+    fn article(headline: &str, content: &str) -> NewsArticle {
+        NewsArticle::new(headline, "", "", content)
+    }
+
+    #[test]
+    fn estimates_similarity() {
+        let detector = Detector::default();
+        let a = article(
+            "Penguins win the Stanley Cup",
+            "They beat Nashville in six games.",
+        );
+        let b = article(
+            "Penguins win the Stanley Cup",
+            "They beat Nashville in six games!",
+        );
+        let c = article("Snow expected downtown", "Bring a shovel.");
+
+        assert_eq!(detector.similarity(&a, &b), 1.0);
+        assert!(detector.similarity(&a, &c) < 0.1);
+        assert_eq!(detector.similarity(&article("", ""), &article("", "")), 0.0);
+        assert_eq!(detector.signature(&a).minimums.len(), 128);
+    }
+
+    #[test]
+    fn clusters_and_picks_canonicals() {
+        let detector = Detector::new(DuplicateOptions {
+            threshold: 0.9,
+            ..DuplicateOptions::default()
+        });
+        let articles = [
+            article("Snow expected downtown", "Bring a shovel."),
+            article("Penguins win the Cup", "They beat Nashville."),
+            article("Penguins win the Cup", "They beat Nashville in six games."),
+            article("Snow expected downtown", "Bring a shovel."),
+            article("Penguins win the Cup", "They beat Nashville in six."),
+        ];
+
+        let clusters = detector.clusters(&articles);
+        assert_eq!(clusters.len(), 4);
+        assert_eq!(
+            clusters[0],
+            Cluster {
+                canonical: 0,
+                members: vec![0, 3]
+            }
+        );
+        assert_eq!(clusters[1].members, [1]);
+        assert_eq!(clusters[2].members, [2]);
+        assert_eq!(clusters[3].members, [4]);
+
+        let loose = Detector::new(DuplicateOptions {
+            threshold: 0.5,
+            ..DuplicateOptions::default()
+        });
+        let clusters = loose.clusters(&articles);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[1].members, [1, 2, 4]);
+        // The middle rewrite shares the most with both of the others
+        assert_eq!(clusters[1].canonical, 4);
+        assert_eq!(loose.canonical(&articles), [&articles[0], &articles[4]]);
+    }
+}
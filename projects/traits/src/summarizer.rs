@@ -0,0 +1,182 @@
+//! Extractive summaries: the few sentences of a text that best stand for
+//! the whole, picked rather than rewritten. A sentence scores for using the
+//! text's most frequent words, and the opening sentences score extra, since
+//! news puts the point first.
+
+use crate::search::tokenize;
+use std::collections::HashMap;
+
+// Titles and such that end in a period without ending the sentence. "No."
+// is only one before a number, since "no." is so often just no.
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "st", "sr", "jr", "vs", "etc", "inc", "ltd", "co", "gov",
+    "sen", "rep", "gen", "col", "lt", "jan", "feb", "mar", "apr", "jun", "jul", "aug", "sep",
+    "sept", "oct", "nov", "dec",
+];
+
+/// Split text into sentences, trimmed. A sentence ends at `.`, `!` or `?`
+/// (and any closing quotes or brackets) followed by a space, unless the
+/// word before is an abbreviation or an initial, as in "Dr.", "J." or
+/// "No. 5", or the next word starts in lowercase.
+pub fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((at, c)) = chars.next() {
+        if !matches!(c, '.' | '!' | '?') {
+            continue;
+        }
+        let mut end = at + c.len_utf8();
+        while let Some(&(next, closing)) = chars.peek() {
+            if !matches!(closing, '.' | '!' | '?' | '"' | '\'' | '”' | '’' | ')') {
+                break;
+            }
+            end = next + closing.len_utf8();
+            chars.next();
+        }
+        let at_break = chars.peek().is_none_or(|&(_, next)| next.is_whitespace());
+        // The next sentence shouldn't start in lowercase
+        let next = text[end..].trim_start();
+        let continues = next.starts_with(|next: char| next.is_lowercase());
+        if !at_break || continues || (c == '.' && abbreviated(&text[start..at], next)) {
+            continue;
+        }
+        push_sentence(&mut sentences, &text[start..end]);
+        start = end;
+    }
+    push_sentence(&mut sentences, &text[start..]);
+    sentences
+}
+
+fn push_sentence<'a>(sentences: &mut Vec<&'a str>, sentence: &'a str) {
+    let sentence = sentence.trim();
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+}
+
+// Whether the word before a period is one that doesn't end sentences
+fn abbreviated(before: &str, after: &str) -> bool {
+    let word = before
+        .rsplit(|c: char| c.is_whitespace() || c == '(' || c == '"')
+        .next()
+        .unwrap_or("");
+    let single_letter = word.chars().count() == 1 && word.chars().all(char::is_uppercase);
+    // U.S, e.g and other dotted abbreviations, before their last period,
+    // but not prices like $3.50
+    let dotted = word.contains('.') && word.contains(char::is_alphabetic);
+    let number = word.eq_ignore_ascii_case("no") && after.starts_with(|c: char| c.is_ascii_digit());
+    single_letter || dotted || number || ABBREVIATIONS.contains(&word.to_lowercase().as_str())
+}
+
+/// The count best sentences of text, in the order they appear, joined by
+/// spaces. Text with count or fewer sentences comes back whole.
+pub fn extract(text: &str, count: usize) -> String {
+    let sentences = sentences(text);
+    if sentences.len() <= count {
+        return sentences.join(" ");
+    }
+
+    let words: Vec<Vec<String>> = sentences
+        .iter()
+        .map(|sentence| tokenize(sentence))
+        .collect();
+    let mut frequencies: HashMap<&str, f64> = HashMap::new();
+    for word in words.iter().flatten() {
+        *frequencies.entry(word).or_default() += 1.0;
+    }
+    let top = frequencies.values().copied().fold(1.0, f64::max);
+
+    let scores: Vec<f64> = words
+        .iter()
+        .enumerate()
+        .map(|(position, words)| {
+            // Average rather than total, so long sentences don't win just
+            // for being long
+            let frequency = words
+                .iter()
+                .map(|word| frequencies[word.as_str()] / top)
+                .sum::<f64>()
+                / (words.len().max(1) as f64);
+            let lead = 1.0 + 0.5 / (position + 1) as f64;
+            frequency * lead
+        })
+        .collect();
+
+    let mut ranked: Vec<usize> = (0..sentences.len()).collect();
+    ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]).then(a.cmp(&b)));
+    ranked.truncate(count);
+    ranked.sort_unstable();
+    ranked
+        .into_iter()
+        .map(|index| sentences[index])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewsArticle, Summary, Tweet, TweetType};
+
+    const STORY: &str = "The Pittsburgh Penguins won the Stanley Cup on Sunday. \
+        It was a cold night in Nashville. \
+        The Penguins beat the Predators 2-0, and Penguins fans filled the streets. \
+        Dr. Smith, a longtime fan, brought his dog. \
+        The Stanley Cup parade for the Penguins is on Wednesday.";
+
+    #[test]
+    fn splits_sentences() {
+        assert_eq!(
+            sentences("Mr. Rogers said hi. Did he? \"Yes!\" he did... U.S. news (A. Smith).  "),
+            [
+                "Mr. Rogers said hi.",
+                "Did he?",
+                "\"Yes!\" he did...",
+                "U.S. news (A. Smith)."
+            ]
+        );
+        assert_eq!(sentences("3.14 is pi. No end"), ["3.14 is pi.", "No end"]);
+        assert_eq!(
+            sentences("She said no. Then she left. It was No. 5 on the list."),
+            [
+                "She said no.",
+                "Then she left.",
+                "It was No. 5 on the list."
+            ]
+        );
+        assert_eq!(
+            sentences("It cost $3.50. Then it rose."),
+            ["It cost $3.50.", "Then it rose."]
+        );
+        assert!(sentences("  ").is_empty());
+    }
+
+    #[test]
+    fn picks_sentences_about_the_story() {
+        assert_eq!(
+            extract(STORY, 2),
+            "The Pittsburgh Penguins won the Stanley Cup on Sunday. \
+             The Stanley Cup parade for the Penguins is on Wednesday."
+        );
+        assert_eq!(extract(STORY, 10), sentences(STORY).join(" "));
+        assert_eq!(extract(STORY, 0), "");
+        assert_eq!(extract("", 3), "");
+    }
+
+    #[test]
+    fn excerpts_through_summary() {
+        let article = NewsArticle::new("Penguins win", "Pittsburgh", "Iceburgh", STORY);
+        assert_eq!(
+            article.excerpt(1),
+            "The Pittsburgh Penguins won the Stanley Cup on Sunday."
+        );
+
+        let empty = NewsArticle::new("Penguins win", "Pittsburgh", "Iceburgh", "");
+        assert_eq!(empty.excerpt(1), empty.summarize());
+
+        let tweet = Tweet::new("dril", "no. absolutely not.", TweetType::Tweet, None).unwrap();
+        assert_eq!(tweet.excerpt(3), "no. absolutely not.");
+    }
+}
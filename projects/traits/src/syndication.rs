@@ -0,0 +1,465 @@
+//! Reading news feeds (RSS 2.0, RSS 1.0 and Atom) into NewsArticles.
+//!
+//! Feeds are often sloppy, so missing fields become empty strings or None
+//! rather than errors. Markup in descriptions and HTML content is stripped
+//! down to plain text. Feeds don't carry a location, so every article's
+//! location is empty.
+
+use crate::xml::{self, Element, Node};
+use crate::NewsArticle;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One item (RSS) or entry (Atom) from a feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedEntry {
+    pub article: NewsArticle,
+    pub published: Option<SystemTime>,
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input couldn't be read as XML at all.
+    Malformed {
+        offset: usize,
+        message: &'static str,
+    },
+    /// The document is XML, but its root element isn't a feed.
+    NotAFeed(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Malformed { offset, message } => {
+                write!(f, "malformed feed: {message} at byte {offset}")
+            }
+            ParseError::NotAFeed(root) => write!(f, "<{root}> is not an RSS or Atom feed"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Every item or entry in an RSS or Atom document, in document order.
+pub fn parse(input: &str) -> Result<Vec<FeedEntry>, ParseError> {
+    let root = xml::parse(input).map_err(|err| ParseError::Malformed {
+        offset: err.offset,
+        message: err.message,
+    })?;
+
+    let entries = match root.local_name() {
+        "rss" => match root.child("channel") {
+            Some(channel) => channel
+                .children_named("item")
+                .map(|item| rss_item(item, Some(channel)))
+                .collect(),
+            None => Vec::new(),
+        },
+        // RSS 1.0 puts its items next to the channel instead of inside it
+        "RDF" => root
+            .children_named("item")
+            .map(|item| rss_item(item, root.child("channel")))
+            .collect(),
+        "feed" => root
+            .children_named("entry")
+            .map(|entry| atom_entry(entry, &root))
+            .collect(),
+        other => return Err(ParseError::NotAFeed(other.to_string())),
+    };
+    Ok(entries)
+}
+
+fn rss_item(item: &Element, channel: Option<&Element>) -> FeedEntry {
+    let text = |name: &str| item.child(name).map(|e| collapse(&e.text()));
+
+    let headline = text("title").unwrap_or_default();
+    let author = item
+        .child("author")
+        .or_else(|| item.child("creator"))
+        .or_else(|| channel?.child("managingEditor"))
+        .map(|e| rss_person(&e.text()))
+        .unwrap_or_default();
+    // content:encoded holds the full text when there is one
+    let content = item
+        .child("encoded")
+        .or_else(|| item.child("description"))
+        .map(|e| strip_markup(&e.text()))
+        .unwrap_or_default();
+
+    let published = match (text("pubDate"), text("date")) {
+        (Some(date), _) => parse_rfc822(&date),
+        (None, Some(date)) => parse_rfc3339(&date),
+        (None, None) => None,
+    };
+
+    FeedEntry {
+        article: NewsArticle::new(&headline, "", &author, &content),
+        published,
+        link: text("link").filter(|link| !link.is_empty()),
+    }
+}
+
+fn atom_entry(entry: &Element, feed: &Element) -> FeedEntry {
+    let headline = entry.child("title").map(atom_text).unwrap_or_default();
+    let author = entry
+        .child("author")
+        .or_else(|| feed.child("author"))
+        .and_then(|author| author.child("name"))
+        .map(|name| collapse(&name.text()))
+        .unwrap_or_default();
+    let content = entry
+        .child("content")
+        .or_else(|| entry.child("summary"))
+        .map(atom_text)
+        .unwrap_or_default();
+
+    let published = entry
+        .child("published")
+        .or_else(|| entry.child("updated"))
+        .and_then(|date| parse_rfc3339(&date.text()));
+    let link = entry
+        .children_named("link")
+        .find(|link| matches!(link.attribute("rel"), None | Some("alternate")))
+        .and_then(|link| link.attribute("href"))
+        .map(str::to_string);
+
+    FeedEntry {
+        article: NewsArticle::new(&headline, "", &author, &content),
+        published,
+        link,
+    }
+}
+
+/// The text of an Atom text construct, which is plain text, escaped HTML or
+/// inline XHTML depending on its type attribute.
+fn atom_text(element: &Element) -> String {
+    match element.attribute("type") {
+        Some("html" | "text/html") => strip_markup(&element.text()),
+        Some("xhtml" | "application/xhtml+xml") => {
+            let mut text = String::new();
+            xhtml_text(element, &mut text);
+            collapse(&text)
+        }
+        _ => collapse(&element.text()),
+    }
+}
+
+fn xhtml_text(element: &Element, text: &mut String) {
+    for node in &element.children {
+        match node {
+            Node::Text(part) => text.push_str(part),
+            Node::Element(child) => {
+                xhtml_text(child, text);
+                if is_block(child.local_name()) {
+                    text.push(' ');
+                }
+            }
+        }
+    }
+}
+
+/// RSS people are usually written "email (Name)". Keep just the name when
+/// there is one.
+fn rss_person(text: &str) -> String {
+    let text = collapse(text);
+    match (text.find('('), text.rfind(')')) {
+        (Some(open), Some(close)) if open < close => text[open + 1..close].trim().to_string(),
+        _ => text,
+    }
+}
+
+/// Plain text from a fragment of HTML: tags are dropped (block tags leave a
+/// space so paragraphs don't run together), entities are decoded and
+/// whitespace is collapsed. A `<` that doesn't start a tag is kept.
+fn strip_markup(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(open) = rest.find('<') {
+        text.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let starts_tag = rest[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '/' | '!'));
+        match rest.find('>') {
+            Some(close) if starts_tag => {
+                let name = rest[1..close]
+                    .trim_start_matches('/')
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .next()
+                    .unwrap_or("");
+                if is_block(&name.to_ascii_lowercase()) {
+                    text.push(' ');
+                }
+                rest = &rest[close + 1..];
+            }
+            _ => {
+                text.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+
+    collapse(&xml::decode_entities(&text))
+}
+
+/// Tags that break a line when HTML is rendered.
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "br",
+    "div",
+    "li",
+    "ul",
+    "ol",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "tr",
+    "td",
+    "hr",
+];
+
+fn is_block(name: &str) -> bool {
+    BLOCK_TAGS.contains(&name)
+}
+
+/// Trim, and turn every run of whitespace into a single space.
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// An RSS date like "Sat, 07 Sep 2002 00:00:01 GMT". The day of the week and
+/// the seconds are optional, and the zone can be an offset like -0500 or one
+/// of the names RFC 822 allows.
+fn parse_rfc822(text: &str) -> Option<SystemTime> {
+    let text = text.split_once(',').map_or(text, |(_, rest)| rest);
+    let mut parts = text.split_whitespace();
+
+    let day = parts.next()?.parse().ok()?;
+    let month = month_number(parts.next()?)?;
+    let year: i64 = parts.next()?.parse().ok()?;
+    // Two-digit years, as RFC 2822 reads them
+    let year = match year {
+        0..=49 => year + 2000,
+        50..=99 => year + 1900,
+        _ => year,
+    };
+
+    let mut time = parts.next()?.split(':');
+    let hour = time.next()?.parse().ok()?;
+    let minute = time.next()?.parse().ok()?;
+    let second = match time.next() {
+        Some(second) => second.parse().ok()?,
+        None => 0,
+    };
+
+    let offset = match parts.next() {
+        Some(zone) => zone_offset(zone)?,
+        None => 0,
+    };
+    to_system_time(year, month, day, [hour, minute, second], offset)
+}
+
+/// An Atom date like "2003-12-13T18:30:02Z" or "2003-12-13T18:30:02.25+01:00".
+/// Fractions of a second are dropped.
+fn parse_rfc3339(text: &str) -> Option<SystemTime> {
+    let text = text.trim();
+    let number = |range: std::ops::Range<usize>| text.get(range)?.parse::<u32>().ok();
+
+    let year = number(0..4)? as i64;
+    let month = number(5..7)?;
+    let day = number(8..10)?;
+    let hour = number(11..13)?;
+    let minute = number(14..16)?;
+    let second = number(17..19)?;
+    if text.get(4..5)? != "-"
+        || text.get(7..8)? != "-"
+        || !matches!(text.get(10..11)?, "T" | "t" | " ")
+    {
+        return None;
+    }
+
+    let mut zone = text.get(19..)?;
+    if let Some(fraction) = zone.strip_prefix('.') {
+        zone = fraction.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    let offset = match zone {
+        // A missing zone is taken as UTC
+        "" | "Z" | "z" => 0,
+        _ => zone_offset(&zone.replace(':', ""))?,
+    };
+    to_system_time(year, month, day, [hour, minute, second], offset)
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    let months = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let name = name.get(..3)?.to_ascii_lowercase();
+    months
+        .iter()
+        .position(|month| *month == name)
+        .map(|i| i as u32 + 1)
+}
+
+/// Seconds east of UTC for "+0130", "-0500" or a named zone.
+fn zone_offset(zone: &str) -> Option<i64> {
+    let hours = match zone.to_ascii_uppercase().as_str() {
+        "GMT" | "UT" | "UTC" | "Z" => 0,
+        "EDT" => -4,
+        "EST" | "CDT" => -5,
+        "CST" | "MDT" => -6,
+        "MST" | "PDT" => -7,
+        "PST" => -8,
+        _ => {
+            let sign = match zone.get(..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let digits = zone.get(1..)?;
+            if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let hours: i64 = digits[..2].parse().ok()?;
+            let minutes: i64 = digits[2..].parse().ok()?;
+            return Some(sign * (hours * 3600 + minutes * 60));
+        }
+    };
+    Some(hours * 3600)
+}
+
+fn to_system_time(
+    year: i64,
+    month: u32,
+    day: u32,
+    [h, m, s]: [u32; 3],
+    offset: i64,
+) -> Option<SystemTime> {
+    // 60 seconds allows for leap seconds. Years past 9999 are garbage, and
+    // far enough out would overflow the day count.
+    if !(1..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || day == 0
+        || day > days_in_month(year, month)
+        || h > 23
+        || m > 59
+        || s > 60
+    {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    let secs = days * 86_400 + (h * 3600 + m * 60 + s) as i64 - offset;
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    }
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given date in the proleptic Gregorian
+/// calendar, negative before it. This is Howard Hinnant's days_from_civil.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn reads_rss_dates() {
+        assert_eq!(
+            parse_rfc822("Sat, 07 Sep 2002 00:00:01 GMT"),
+            at(1_031_356_801)
+        );
+        assert_eq!(
+            parse_rfc822("07 Sep 2002 00:00:01 +0000"),
+            at(1_031_356_801)
+        );
+        assert_eq!(
+            parse_rfc822("Fri, 06 Sep 2002 19:00:01 EST"),
+            at(1_031_356_801)
+        );
+        assert_eq!(
+            parse_rfc822("Sat, 07 Sep 02 01:30 +0130"),
+            at(1_031_356_800)
+        );
+        assert_eq!(
+            parse_rfc822("Thu, 29 Feb 2024 12:00:00 Z"),
+            at(1_709_208_000)
+        );
+        assert_eq!(parse_rfc822("Thu, 30 Feb 2024 12:00:00 Z"), None);
+        assert_eq!(parse_rfc822("Sat, 07 Sep 2002 00:00:01 Mars"), None);
+        assert_eq!(
+            parse_rfc822("Sat, 07 Sep 99999999999999999 00:00:01 GMT"),
+            None
+        );
+        assert_eq!(parse_rfc822("Sat, 07 Sep -5 00:00:01 GMT"), None);
+        assert_eq!(parse_rfc822("yesterday"), None);
+    }
+
+    #[test]
+    fn reads_atom_dates() {
+        assert_eq!(parse_rfc3339("2002-09-07T00:00:01Z"), at(1_031_356_801));
+        assert_eq!(
+            parse_rfc3339("2002-09-07t02:00:01.999+02:00"),
+            at(1_031_356_801)
+        );
+        assert_eq!(
+            parse_rfc3339("2002-09-06 20:00:01-04:00"),
+            at(1_031_356_801)
+        );
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), at(0));
+        assert_eq!(
+            parse_rfc3339("1969-12-31T23:59:59Z"),
+            UNIX_EPOCH.checked_sub(Duration::from_secs(1))
+        );
+        assert_eq!(parse_rfc3339("2002-13-07T00:00:01Z"), None);
+        assert_eq!(parse_rfc3339("2002-09-07"), None);
+    }
+
+    #[test]
+    fn strips_markup() {
+        assert_eq!(
+            strip_markup("<p>One <b>bold</b> move.</p><p>Two&amp;three</p>"),
+            "One bold move. Two&three"
+        );
+        assert_eq!(strip_markup("a < b and c > d"), "a < b and c > d");
+        assert_eq!(strip_markup("line<br/>break"), "line break");
+        assert_eq!(rss_person("desk@example.com (Desk Editor)"), "Desk Editor");
+        assert_eq!(rss_person("  Al  Roker "), "Al Roker");
+    }
+}
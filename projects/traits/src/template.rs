@@ -0,0 +1,355 @@
+//! Summaries rendered through templates instead of each type's fixed format.
+//!
+//! A template is text with fields in braces, such as
+//! `"{headline} — {author}"`. Part of a template can go in square brackets
+//! to make it optional: `"{headline}[, by {author}]"` drops the ", by " along
+//! with the author when there isn't one. A backslash makes the character
+//! after it literal, so `\{` is a brace.
+//!
+//! Every Summary has `summary` and `author` fields, and types add their own
+//! through Summary::field. A field the item doesn't have renders as nothing.
+
+use crate::Summary;
+use std::error::Error;
+use std::fmt;
+
+/// What goes on the end of a summary that had to be cut short.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Ellipsis {
+    /// "…"
+    #[default]
+    Unicode,
+    /// "..."
+    Ascii,
+    Text(String),
+    None,
+}
+
+impl Ellipsis {
+    fn as_str(&self) -> &str {
+        match self {
+            Ellipsis::Unicode => "…",
+            Ellipsis::Ascii => "...",
+            Ellipsis::Text(text) => text,
+            Ellipsis::None => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaryOptions {
+    /// Longest the summary can be, in chars, ellipsis included. Longer
+    /// summaries are cut at a word boundary where there is one.
+    pub max_length: Option<usize>,
+    pub ellipsis: Ellipsis,
+    pub include_author: bool,
+    pub include_location: bool,
+    /// None renders the item's own Summary::default_template.
+    pub template: Option<Template>,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        Self {
+            max_length: None,
+            ellipsis: Ellipsis::default(),
+            include_author: true,
+            include_location: true,
+            template: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A `{` with no `}` after it.
+    UnclosedField { offset: usize },
+    /// A `[` with no `]` after it.
+    UnclosedGroup { offset: usize },
+    /// A `}` or `]` that nothing opened.
+    Unmatched { offset: usize },
+    /// `{}`, or a field name with a brace in it.
+    BadField { offset: usize },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnclosedField { offset } => write!(f, "unclosed {{ at {offset}"),
+            TemplateError::UnclosedGroup { offset } => write!(f, "unclosed [ at {offset}"),
+            TemplateError::Unmatched { offset } => write!(f, "nothing to close at {offset}"),
+            TemplateError::BadField { offset } => write!(f, "bad field name at {offset}"),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Field(String),
+    Group(Vec<Piece>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        // Groups being filled in, innermost last, with where each opened
+        let mut open: Vec<(usize, Vec<Piece>)> = vec![(0, Vec::new())];
+        let mut text = String::new();
+        let mut chars = source.char_indices().peekable();
+
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '\\' => text.extend(chars.next().map(|(_, next)| next)),
+                '{' => {
+                    let rest = &source[offset + 1..];
+                    let end = rest
+                        .find('}')
+                        .ok_or(TemplateError::UnclosedField { offset })?;
+                    let name = rest[..end].trim();
+                    if name.is_empty() || name.contains('{') {
+                        return Err(TemplateError::BadField { offset });
+                    }
+                    push_text(&mut open, &mut text);
+                    current(&mut open).push(Piece::Field(name.to_string()));
+                    while chars.next_if(|&(at, _)| at <= offset + end).is_some() {}
+                    chars.next();
+                }
+                '[' => {
+                    push_text(&mut open, &mut text);
+                    open.push((offset, Vec::new()));
+                }
+                ']' if open.len() > 1 => {
+                    push_text(&mut open, &mut text);
+                    let (_, group) = open.pop().unwrap();
+                    current(&mut open).push(Piece::Group(group));
+                }
+                '}' | ']' => return Err(TemplateError::Unmatched { offset }),
+                _ => text.push(c),
+            }
+        }
+
+        push_text(&mut open, &mut text);
+        if open.len() > 1 {
+            let (offset, _) = open.pop().unwrap();
+            return Err(TemplateError::UnclosedGroup { offset });
+        }
+        Ok(Self {
+            pieces: open.pop().unwrap().1,
+        })
+    }
+
+    /// Fill the template in from item. Fields turned off in options count
+    /// as missing.
+    pub fn render(&self, item: &(impl Summary + ?Sized), options: &SummaryOptions) -> String {
+        let lookup = |name: &str| -> Option<String> {
+            let value = match name {
+                "author" if !options.include_author => None,
+                "location" if !options.include_location => None,
+                "summary" => Some(item.summarize()),
+                "author" => item.author().map(String::from),
+                _ => item.field(name),
+            };
+            value.filter(|value| !value.is_empty())
+        };
+
+        let mut out = String::new();
+        fill(&self.pieces, &lookup, &mut out);
+        out
+    }
+}
+
+impl std::str::FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+fn current(open: &mut [(usize, Vec<Piece>)]) -> &mut Vec<Piece> {
+    &mut open.last_mut().unwrap().1
+}
+
+fn push_text(open: &mut [(usize, Vec<Piece>)], text: &mut String) {
+    if !text.is_empty() {
+        current(open).push(Piece::Text(std::mem::take(text)));
+    }
+}
+
+// Returns false if a field was missing, which drops the group it's in
+fn fill(pieces: &[Piece], lookup: &dyn Fn(&str) -> Option<String>, out: &mut String) -> bool {
+    let mut complete = true;
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => out.push_str(text),
+            Piece::Field(name) => match lookup(name) {
+                Some(value) => out.push_str(&value),
+                None => complete = false,
+            },
+            Piece::Group(group) => {
+                let mut inner = String::new();
+                if fill(group, lookup, &mut inner) {
+                    out.push_str(&inner);
+                }
+            }
+        }
+    }
+    complete
+}
+
+/// Render item with options: through options.template if there is one,
+/// otherwise the item's own default template, then cut down to
+/// options.max_length.
+pub fn render(item: &(impl Summary + ?Sized), options: &SummaryOptions) -> String {
+    let rendered = match &options.template {
+        Some(template) => template.render(item, options),
+        None => Template::parse(item.default_template())
+            .expect("default templates parse")
+            .render(item, options),
+    };
+    match options.max_length {
+        Some(max) => truncate(&rendered, max, options.ellipsis.as_str()),
+        None => rendered,
+    }
+}
+
+/// Cut text down to max chars, ellipsis included, ending at a word
+/// boundary if there is one in the part kept.
+pub fn truncate(text: &str, max: usize, ellipsis: &str) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let room = max.saturating_sub(ellipsis.chars().count());
+    if room == 0 {
+        return ellipsis.chars().take(max).collect();
+    }
+
+    let cut = text
+        .char_indices()
+        .nth(room)
+        .map_or(text.len(), |(at, _)| at);
+    let mut kept = &text[..cut];
+    // Unless the cut happens to fall between words, back up to the last one
+    if !text[cut..].starts_with(char::is_whitespace) {
+        if let Some(space) = kept.rfind(char::is_whitespace) {
+            kept = &kept[..space];
+        }
+    }
+    let kept = kept.trim_end_matches(|c: char| c.is_whitespace() || ",;:—-".contains(c));
+    format!("{kept}{ellipsis}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewsArticle, Tweet, TweetType};
+
+    struct Untitled;
+
+    impl Summary for Untitled {}
+
+    fn article() -> NewsArticle {
+        NewsArticle::new(
+            "Penguins win the Stanley Cup Championship!",
+            "Pittsburgh, PA, USA",
+            "Iceburgh",
+            "The Pittsburgh Penguins once again are the best hockey team in the NHL.",
+        )
+    }
+
+    #[test]
+    fn parses_templates() {
+        assert!(Template::parse("{headline} — {author}").is_ok());
+        assert!(Template::parse(r"\{literal\} \[x\] [a [{b}]]").is_ok());
+        assert_eq!(
+            Template::parse("{headline"),
+            Err(TemplateError::UnclosedField { offset: 0 })
+        );
+        assert_eq!(
+            Template::parse("x [{a}"),
+            Err(TemplateError::UnclosedGroup { offset: 2 })
+        );
+        assert_eq!(
+            Template::parse("a}"),
+            Err(TemplateError::Unmatched { offset: 1 })
+        );
+        assert_eq!(
+            Template::parse("a {}"),
+            Err(TemplateError::BadField { offset: 2 })
+        );
+    }
+
+    #[test]
+    fn fills_fields_and_groups() {
+        let template: Template = "{headline} — {author}[ ({location})]".parse().unwrap();
+        let options = SummaryOptions::default();
+        assert_eq!(
+            template.render(&article(), &options),
+            "Penguins win the Stanley Cup Championship! — Iceburgh (Pittsburgh, PA, USA)"
+        );
+
+        let options = SummaryOptions {
+            include_location: false,
+            ..SummaryOptions::default()
+        };
+        assert_eq!(
+            template.render(&article(), &options),
+            "Penguins win the Stanley Cup Championship! — Iceburgh"
+        );
+
+        let literal: Template = r"\{{summary}\} \[{nothing}\] [x{nothing}]\\"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            literal.render(&Untitled, &options),
+            "{(Read more...)} [] \\"
+        );
+    }
+
+    #[test]
+    fn defaults_match_summarize() {
+        let tweet = Tweet::new("horse_ebooks", "of course", TweetType::Tweet, None).unwrap();
+        let options = SummaryOptions::default();
+        assert_eq!(render(&article(), &options), article().summarize());
+        assert_eq!(render(&tweet, &options), tweet.summarize());
+        assert_eq!(render(&Untitled, &options), "(Read more...)");
+        assert_eq!(Untitled.render(&options), "(Read more...)");
+
+        let anonymous = SummaryOptions {
+            include_author: false,
+            include_location: false,
+            ..SummaryOptions::default()
+        };
+        assert_eq!(
+            article().render(&anonymous),
+            "Penguins win the Stanley Cup Championship!"
+        );
+        assert_eq!(tweet.render(&anonymous), "of course");
+    }
+
+    #[test]
+    fn truncates_at_words() {
+        assert_eq!(truncate("short", 10, "…"), "short");
+        assert_eq!(truncate("one two three", 10, "…"), "one two…");
+        assert_eq!(truncate("one two three", 8, "..."), "one...");
+        assert_eq!(truncate("one two, three", 9, "…"), "one two…");
+        assert_eq!(truncate("unbreakable", 6, "…"), "unbre…");
+        assert_eq!(truncate("日本語のツイート", 4, ""), "日本語の");
+        assert_eq!(truncate("anything", 2, "..."), "..");
+
+        let options = SummaryOptions {
+            max_length: Some(30),
+            ellipsis: Ellipsis::Text(String::from(" (more)")),
+            template: Some("{content}".parse().unwrap()),
+            ..SummaryOptions::default()
+        };
+        assert_eq!(article().render(&options), "The Pittsburgh Penguins (more)");
+    }
+}
@@ -0,0 +1,339 @@
+//! Tweets with ids, so replies and retweets can point at the tweets they
+//! answer or repeat. A Timeline checks those pointers as tweets are posted
+//! and can put reply threads back together as trees.
+
+use crate::{Summary, Tweet, TweetType};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimelineError {
+    /// The tweet replies to or retweets an id that hasn't been posted.
+    UnknownTweet(u32),
+    /// A retweet has to say which tweet it repeats.
+    RetweetOfNothing,
+}
+
+impl fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineError::UnknownTweet(id) => write!(f, "there is no tweet {id}"),
+            TimelineError::RetweetOfNothing => write!(f, "retweet doesn't say what it retweets"),
+        }
+    }
+}
+
+impl Error for TimelineError {}
+
+struct Entry {
+    tweet: Tweet,
+    replies: Vec<u32>,
+    retweets: u32,
+}
+
+/// Every tweet posted so far. Ids count up from 1 in the order tweets are
+/// posted, so a reply always has a higher id than the tweet it answers.
+#[derive(Default)]
+pub struct Timeline {
+    entries: Vec<Entry>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a tweet and return its id. Replies and retweets must point at a
+    /// tweet that is already on the timeline. Retweeting a retweet counts
+    /// toward the original.
+    pub fn post(&mut self, tweet: Tweet) -> Result<u32, TimelineError> {
+        let target = tweet.replied_to();
+        if let Some(target) = target {
+            if self.get(target).is_none() {
+                return Err(TimelineError::UnknownTweet(target));
+            }
+        }
+
+        let id = u32::try_from(self.entries.len() + 1).expect("fewer than 4 billion tweets");
+        match (tweet.tweet_type(), target) {
+            (TweetType::Reply, Some(target)) => self.entry_mut(target).replies.push(id),
+            (TweetType::Retweet, Some(target)) => {
+                let original = self.original(target);
+                self.entry_mut(original).retweets += 1;
+            }
+            (TweetType::Retweet, None) => return Err(TimelineError::RetweetOfNothing),
+            _ => (),
+        }
+
+        self.entries.push(Entry {
+            tweet,
+            replies: Vec::new(),
+            retweets: 0,
+        });
+        Ok(id)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Tweet> {
+        self.entry(id).map(|entry| &entry.tweet)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// How many times the tweet has been retweeted.
+    pub fn retweet_count(&self, id: u32) -> Option<u32> {
+        self.entry(id).map(|entry| entry.retweets)
+    }
+
+    /// Ids of the direct replies to a tweet, oldest first.
+    pub fn replies(&self, id: u32) -> &[u32] {
+        self.entry(id).map_or(&[], |entry| &entry.replies)
+    }
+
+    /// The tweet that started the conversation id is part of.
+    pub fn conversation_root(&self, mut id: u32) -> Option<u32> {
+        let mut tweet = self.get(id)?;
+        while let (TweetType::Reply, Some(parent)) = (tweet.tweet_type(), tweet.replied_to()) {
+            id = parent;
+            tweet = self.get(id)?;
+        }
+        Some(id)
+    }
+
+    /// The tweet and every reply under it, as a tree.
+    pub fn thread(&self, id: u32) -> Option<Thread<'_>> {
+        self.entry(id)?;
+        // Every tweet in the thread, each before its replies. Reply chains
+        // can be as long as the timeline, so no recursion.
+        let mut order = Vec::new();
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            order.push(id);
+            pending.extend(self.replies(id).iter().rev());
+        }
+
+        // Built from the bottom up, so replies are ready for their parents
+        let mut built: HashMap<u32, Thread<'_>> = HashMap::new();
+        for &id in order.iter().rev() {
+            let entry = &self.entries[id as usize - 1];
+            let thread = Thread {
+                id,
+                tweet: &entry.tweet,
+                retweets: entry.retweets,
+                replies: entry
+                    .replies
+                    .iter()
+                    .filter_map(|reply| built.remove(reply))
+                    .collect(),
+            };
+            built.insert(id, thread);
+        }
+        built.remove(&id)
+    }
+
+    /// The whole conversation id is part of, from its first tweet down.
+    pub fn conversation(&self, id: u32) -> Option<Thread<'_>> {
+        self.thread(self.conversation_root(id)?)
+    }
+
+    fn entry(&self, id: u32) -> Option<&Entry> {
+        self.entries.get((id as usize).checked_sub(1)?)
+    }
+
+    fn entry_mut(&mut self, id: u32) -> &mut Entry {
+        &mut self.entries[id as usize - 1]
+    }
+
+    /// What a chain of retweets ends up pointing at.
+    fn original(&self, mut id: u32) -> u32 {
+        while let Some(tweet) = self.get(id) {
+            match (tweet.tweet_type(), tweet.replied_to()) {
+                (TweetType::Retweet, Some(target)) => id = target,
+                _ => break,
+            }
+        }
+        id
+    }
+}
+
+/// A tweet and the replies under it.
+pub struct Thread<'a> {
+    pub id: u32,
+    pub tweet: &'a Tweet,
+    pub retweets: u32,
+    pub replies: Vec<Thread<'a>>,
+}
+
+impl Thread<'_> {
+    /// How many tweets are in the thread, counting the first.
+    pub fn tweet_count(&self) -> usize {
+        let mut count = 0;
+        let mut pending = vec![self];
+        while let Some(thread) = pending.pop() {
+            count += 1;
+            pending.extend(&thread.replies);
+        }
+        count
+    }
+
+    // Each tweet's line has its own prefix, and the lines under it share
+    // another, which carries on the branches of the tweets above
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pending = vec![(self, String::new(), String::new())];
+        while let Some((thread, line, under)) = pending.pop() {
+            write!(f, "{line}")?;
+            thread.write_line(f)?;
+            for (i, reply) in thread.replies.iter().enumerate().rev() {
+                let last = i + 1 == thread.replies.len();
+                let (branch, indent) = if last {
+                    ("└─ ", "   ")
+                } else {
+                    ("├─ ", "│  ")
+                };
+                pending.push((
+                    reply,
+                    format!("{under}{branch}"),
+                    format!("{under}{indent}"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_line(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {}", self.id, self.tweet.summarize())?;
+        match self.retweets {
+            0 => writeln!(f),
+            1 => writeln!(f, " (1 retweet)"),
+            n => writeln!(f, " ({n} retweets)"),
+        }
+    }
+}
+
+/// The thread as an indented tree, one tweet per line:
+///
+/// ```text
+/// #1 horse_ebooks: everything happens so much (2 retweets)
+/// ├─ #2 dril: no
+/// │  └─ #4 horse_ebooks: yes
+/// └─ #3 wint: agreed
+/// ```
+impl fmt::Display for Thread<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f)
+    }
+}
+
+// Dropping the tree the usual way would recurse once per level too
+impl Drop for Thread<'_> {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.replies);
+        while let Some(mut thread) = pending.pop() {
+            pending.append(&mut thread.replies);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tweet(username: &str, content: &str) -> Tweet {
+        Tweet::new(username, content, TweetType::Tweet, None).unwrap()
+    }
+
+    fn reply(username: &str, content: &str, to: u32) -> Tweet {
+        Tweet::new(username, content, TweetType::Reply, Some(to)).unwrap()
+    }
+
+    fn retweet(username: &str, of: u32) -> Tweet {
+        Tweet::new(username, "", TweetType::Retweet, Some(of)).unwrap()
+    }
+
+    #[test]
+    fn assigns_ids_and_checks_targets() {
+        let mut timeline = Timeline::new();
+        assert_eq!(timeline.post(tweet("a", "first")), Ok(1));
+        assert_eq!(timeline.post(reply("b", "second", 1)), Ok(2));
+        assert_eq!(
+            timeline.post(reply("b", "lost", 7)),
+            Err(TimelineError::UnknownTweet(7))
+        );
+        assert_eq!(
+            timeline.post(Tweet::new("c", "", TweetType::Retweet, None).unwrap()),
+            Err(TimelineError::RetweetOfNothing)
+        );
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline.get(2).unwrap().content(), "second");
+        assert!(timeline.get(0).is_none());
+    }
+
+    #[test]
+    fn counts_retweets_of_the_original() {
+        let mut timeline = Timeline::new();
+        let original = timeline.post(tweet("a", "hello")).unwrap();
+        let first = timeline.post(retweet("b", original)).unwrap();
+        timeline.post(retweet("c", first)).unwrap();
+
+        assert_eq!(timeline.retweet_count(original), Some(2));
+        assert_eq!(timeline.retweet_count(first), Some(0));
+        assert_eq!(timeline.retweet_count(99), None);
+        assert!(timeline.replies(original).is_empty());
+    }
+
+    #[test]
+    fn rebuilds_threads() {
+        let mut timeline = Timeline::new();
+        timeline
+            .post(tweet("horse_ebooks", "everything happens so much"))
+            .unwrap();
+        timeline.post(reply("dril", "no", 1)).unwrap();
+        timeline.post(reply("wint", "agreed", 1)).unwrap();
+        timeline.post(reply("horse_ebooks", "yes", 2)).unwrap();
+        timeline.post(retweet("fan", 1)).unwrap();
+        timeline.post(retweet("fan2", 5)).unwrap();
+        timeline.post(tweet("other", "unrelated")).unwrap();
+
+        assert_eq!(timeline.replies(1), [2, 3]);
+        assert_eq!(timeline.conversation_root(4), Some(1));
+        assert_eq!(timeline.conversation_root(7), Some(7));
+
+        let thread = timeline.conversation(4).unwrap();
+        assert_eq!(thread.tweet_count(), 4);
+        assert_eq!(
+            thread.to_string(),
+            "#1 horse_ebooks: everything happens so much (2 retweets)\n\
+             ├─ #2 dril: no\n\
+             │  └─ #4 horse_ebooks: yes\n\
+             └─ #3 wint: agreed\n"
+        );
+
+        let branch = timeline.thread(2).unwrap();
+        assert_eq!(branch.to_string(), "#2 dril: no\n└─ #4 horse_ebooks: yes\n");
+    }
+
+    #[test]
+    fn handles_long_reply_chains() {
+        let mut timeline = Timeline::new();
+        timeline.post(tweet("a", "start")).unwrap();
+        for id in 1..100_000 {
+            timeline.post(reply("b", "and", id)).unwrap();
+        }
+
+        let thread = timeline.conversation(100_000).unwrap();
+        assert_eq!(thread.tweet_count(), 100_000);
+        drop(thread);
+
+        let short = timeline.thread(99_998).unwrap().to_string();
+        assert_eq!(
+            short,
+            "#99998 b: and\n└─ #99999 b: and\n   └─ #100000 b: and\n"
+        );
+    }
+}
@@ -0,0 +1,441 @@
+//! Just enough XML to read news feeds: elements, attributes, text, CDATA and
+//! entity escapes. Comments, processing instructions and doctypes are
+//! skipped, and namespaces are left as prefixes on the names.
+//!
+//! Feeds in the wild are often not quite well formed, so mismatched end tags
+//! are tolerated rather than rejected, and unknown entities are kept as they
+//! are. Only input that can't be split into tags at all is an error.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlError {
+    /// Byte offset into the input where the problem starts.
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+    },
+    End {
+        name: String,
+    },
+    Text(String),
+}
+
+/// Splits a document into tokens. An empty element like `<link href="x"/>`
+/// comes out as a Start followed by an End.
+pub struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+    pending_end: Option<String>,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            pending_end: None,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn error(&self, message: &'static str) -> XmlError {
+        XmlError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    /// Move past the next `end`, returning everything before it.
+    fn skip_past(&mut self, end: &str, message: &'static str) -> Result<&'a str, XmlError> {
+        let found = self.rest().find(end).ok_or_else(|| self.error(message))?;
+        let skipped = &self.rest()[..found];
+        self.pos += found + end.len();
+        Ok(skipped)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn name(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=' | '<'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn start_tag(&mut self) -> Result<Token, XmlError> {
+        self.pos += 1; // <
+        let name = self.name().to_string();
+        if name.is_empty() {
+            return Err(self.error("tag without a name"));
+        }
+
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                self.pending_end = Some(name.clone());
+                break;
+            } else if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            } else if rest.is_empty() {
+                return Err(self.error("unterminated tag"));
+            }
+
+            let attribute = self.name().to_string();
+            if attribute.is_empty() {
+                return Err(self.error("bad attribute"));
+            }
+            self.skip_whitespace();
+            // HTML-style attributes with no value are allowed
+            if !self.rest().starts_with('=') {
+                attributes.push((attribute, String::new()));
+                continue;
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(quote @ ('"' | '\'')) => quote,
+                _ => return Err(self.error("attribute value without quotes")),
+            };
+            self.pos += 1;
+            let value = self.skip_past(
+                if quote == '"' { "\"" } else { "'" },
+                "unterminated attribute value",
+            )?;
+            attributes.push((attribute, decode_entities(value)));
+        }
+
+        Ok(Token::Start { name, attributes })
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Result<Token, XmlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(name) = self.pending_end.take() {
+            return Some(Ok(Token::End { name }));
+        }
+
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return None;
+            }
+
+            let token = if rest.starts_with("<!--") {
+                self.skip_past("-->", "unterminated comment").map(|_| None)
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                self.pos += rest.len() - cdata.len();
+                self.skip_past("]]>", "unterminated CDATA section")
+                    .map(|text| Some(Token::Text(text.to_string())))
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>", "unterminated processing instruction")
+                    .map(|_| None)
+            } else if rest.starts_with("<!") {
+                // A doctype, possibly with an internal subset in brackets
+                let head = &rest[..rest.find('>').unwrap_or(rest.len())];
+                let end = if head.contains('[') { "]>" } else { ">" };
+                self.skip_past(end, "unterminated doctype").map(|_| None)
+            } else if rest.starts_with("</") {
+                self.pos += 2;
+                self.skip_past(">", "unterminated end tag").map(|name| {
+                    Some(Token::End {
+                        name: name.trim().to_string(),
+                    })
+                })
+            } else if rest.starts_with('<') {
+                self.start_tag().map(Some)
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                self.pos += len;
+                Ok(Some(Token::Text(decode_entities(&rest[..len]))))
+            };
+
+            match token {
+                Ok(Some(token)) => return Some(Ok(token)),
+                Ok(None) => continue,
+                Err(err) => {
+                    // Nothing sensible can follow, so stop here
+                    self.pos = self.input.len();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+// Longer than any entity we decode, leading zeros and all
+const MAX_ENTITY: usize = 10;
+
+/// Replace the five XML entities, `&nbsp;` and numeric character references
+/// with the characters they stand for. Anything else that looks like an
+/// entity is left alone.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        // Only as far as the longest name, so text full of bare ampersands
+        // isn't searched to the end for each one
+        let entity = rest.as_bytes()[1..]
+            .iter()
+            .take(MAX_ENTITY + 1)
+            .position(|&byte| byte == b';')
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => {
+                let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#')?.parse().ok(),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    /// The name without any namespace prefix, so `dc:creator` is `creator`.
+    pub fn local_name(&self) -> &str {
+        local(&self.name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| local(key) == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Child elements with the given local name.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter_map(move |node| match node {
+            Node::Element(element) if element.local_name() == name => Some(element),
+            _ => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|node| match node {
+            Node::Element(element) if element.local_name() == name => Some(element),
+            _ => None,
+        })
+    }
+
+    /// All the text inside the element, including inside its children.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.collect_text(&mut text);
+        text
+    }
+
+    fn collect_text(&self, text: &mut String) {
+        for node in &self.children {
+            match node {
+                Node::Text(part) => text.push_str(part),
+                Node::Element(element) => element.collect_text(text),
+            }
+        }
+    }
+}
+
+fn local(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Build the tree of the document's root element.
+///
+/// An end tag closes the innermost open element with that name, along with
+/// anything left open inside it, and end tags that match nothing are
+/// ignored. Elements still open at the end of the input are closed there.
+pub fn parse(input: &str) -> Result<Element, XmlError> {
+    // The document itself, holding the root element
+    let mut stack = vec![Element::default()];
+
+    for token in Tokenizer::new(input) {
+        match token? {
+            Token::Start { name, attributes } => stack.push(Element {
+                name,
+                attributes,
+                children: Vec::new(),
+            }),
+            Token::End { name } => {
+                let Some(open) = stack[1..].iter().rposition(|e| e.name == name) else {
+                    continue;
+                };
+                while stack.len() > open + 1 {
+                    close(&mut stack);
+                }
+            }
+            Token::Text(text) => {
+                let parent = stack.last_mut().expect("the document is never closed");
+                parent.children.push(Node::Text(text));
+            }
+        }
+    }
+    while stack.len() > 1 {
+        close(&mut stack);
+    }
+
+    let document = stack.pop().expect("the document is never closed");
+    document
+        .children
+        .into_iter()
+        .find_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+        .ok_or(XmlError {
+            offset: input.len(),
+            message: "no root element",
+        })
+}
+
+fn close(stack: &mut Vec<Element>) {
+    let element = stack.pop().expect("only called with open elements");
+    let parent = stack.last_mut().expect("the document is never closed");
+    parent.children.push(Node::Element(element));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<Token> {
+        Tokenizer::new(input).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn tokenizes_tags_text_and_cdata() {
+        let input = r#"<?xml version="1.0"?><!-- hi --><a x='1' y="&lt;2&gt;">one &amp; <![CDATA[<two>]]><b/></a>"#;
+        let start = |name: &str, attributes: &[(&str, &str)]| Token::Start {
+            name: name.to_string(),
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        let end = |name: &str| Token::End {
+            name: name.to_string(),
+        };
+
+        assert_eq!(
+            tokens(input),
+            [
+                start("a", &[("x", "1"), ("y", "<2>")]),
+                Token::Text(String::from("one & ")),
+                Token::Text(String::from("<two>")),
+                start("b", &[]),
+                end("b"),
+                end("a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_entities() {
+        assert_eq!(decode_entities("a &amp; b"), "a & b");
+        assert_eq!(
+            decode_entities("&#8217;&#x2014;&#X41;"),
+            "\u{2019}\u{2014}A"
+        );
+        assert_eq!(
+            decode_entities("AT&T &bogus; &#xZZ; &"),
+            "AT&T &bogus; &#xZZ; &"
+        );
+        assert_eq!(decode_entities("&amp;lt;"), "&lt;");
+        let ampersands = format!("{};", "&".repeat(100_000));
+        assert_eq!(decode_entities(&ampersands), ampersands);
+    }
+
+    #[test]
+    fn reports_broken_markup() {
+        let err = Tokenizer::new("<a><!-- never ends").nth(1).unwrap();
+        assert_eq!(
+            err,
+            Err(XmlError {
+                offset: 3,
+                message: "unterminated comment"
+            })
+        );
+        assert!(parse("<a href=x>").is_err());
+        assert!(parse("just text").is_err());
+    }
+
+    #[test]
+    fn builds_a_tolerant_tree() {
+        let root =
+            parse("<rss><channel><item><title>One</i></title><p>open</item></channel></rss>")
+                .unwrap();
+        let item = root.child("channel").unwrap().child("item").unwrap();
+        assert_eq!(item.child("title").unwrap().text(), "One");
+        // <p> was never closed, so </item> closes it too
+        assert_eq!(item.child("p").unwrap().text(), "open");
+
+        let root = parse(r#"<feed><dc:creator x:lang="en">Al</dc:creator>"#).unwrap();
+        let creator = root.child("creator").unwrap();
+        assert_eq!(creator.name, "dc:creator");
+        assert_eq!(creator.attribute("lang"), Some("en"));
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+use traits::feed::Feed;
+use traits::json::Json;
+use traits::similar::{Detector, DuplicateOptions};
+use traits::NewsArticle;
+
+// Three stories told two or three ways each, a related but different
+// story, and one on its own
+fn paraphrases() -> Vec<NewsArticle> {
+    let json = fs::read_to_string("tests/fixtures/paraphrases.json").unwrap();
+    Vec::<NewsArticle>::from_json(&json).unwrap()
+}
+
+fn members(detector: &Detector, articles: &[NewsArticle]) -> Vec<Vec<usize>> {
+    detector
+        .clusters(articles)
+        .into_iter()
+        .map(|cluster| cluster.members)
+        .collect()
+}
+
+#[test]
+fn clusters_paraphrases() {
+    let articles = paraphrases();
+    let detector = Detector::default();
+
+    assert_eq!(
+        members(&detector, &articles),
+        [vec![0, 1, 2], vec![3, 4], vec![5, 6], vec![7], vec![8]]
+    );
+    // The same teams and city aren't the same story
+    assert!(detector.similarity(&articles[0], &articles[7]) < 0.1);
+
+    let canonical: Vec<&str> = detector
+        .canonical(&articles)
+        .iter()
+        .map(|article| article.headline())
+        .collect();
+    assert_eq!(
+        canonical,
+        [
+            "Penguins win the Stanley Cup for the sixth time",
+            "Council votes for protected bike lanes on Penn and Liberty",
+            "Up to a foot of snow forecast for western Pennsylvania",
+            "Penguins trade veteran defenseman to Nashville",
+            "Library extends weekend hours",
+        ]
+    );
+}
+
+#[test]
+fn thresholds_are_configurable() {
+    let articles = paraphrases();
+
+    let strict = Detector::new(DuplicateOptions {
+        threshold: 0.7,
+        ..DuplicateOptions::default()
+    });
+    assert_eq!(members(&strict, &articles).len(), 8);
+    assert_eq!(members(&strict, &articles)[0], [0, 2]);
+
+    let exact = Detector::new(DuplicateOptions {
+        threshold: 1.0,
+        ..DuplicateOptions::default()
+    });
+    assert_eq!(members(&exact, &articles).len(), articles.len());
+
+    // Longer shingles need longer runs of the same words
+    let long = Detector::new(DuplicateOptions {
+        shingle_size: 4,
+        ..DuplicateOptions::default()
+    });
+    let short = Detector::default();
+    assert!(
+        long.similarity(&articles[0], &articles[1]) < short.similarity(&articles[0], &articles[1])
+    );
+}
+
+#[test]
+fn feeds_keep_one_copy_per_story() {
+    let mut feed = Feed::new();
+    for (i, article) in paraphrases().into_iter().enumerate() {
+        feed.push("wire", UNIX_EPOCH + Duration::from_secs(i as u64), article);
+    }
+
+    assert_eq!(feed.dedup_similar(&Detector::default()), 4);
+    assert_eq!(feed.len(), 5);
+    assert!(feed.iter().any(|entry| entry
+        .item
+        .summarize()
+        .starts_with("Penguins win the Stanley Cup")));
+}
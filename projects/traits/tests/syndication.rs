@@ -0,0 +1,107 @@
+// Parsing sample feeds from tests/fixtures, as the aggregator would
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use traits::syndication::{self, ParseError};
+use traits::Summary;
+
+fn at(secs: u64) -> Option<SystemTime> {
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+#[test]
+fn parses_rss2() {
+    let entries = syndication::parse(include_str!("fixtures/rss2.xml")).unwrap();
+    assert_eq!(entries.len(), 4);
+
+    let penguins = &entries[0].article;
+    assert_eq!(penguins.headline(), "Penguins win the Stanley Cup");
+    assert_eq!(penguins.author(), Some("Iceburgh"));
+    assert_eq!(penguins.location(), "");
+    assert_eq!(
+        penguins.content(),
+        "The Penguins beat the Predators & won again. Fans celebrated downtown."
+    );
+    assert_eq!(entries[0].published, at(1_497_238_200));
+    assert_eq!(
+        entries[0].link.as_deref(),
+        Some("https://example.com/penguins")
+    );
+
+    // content:encoded wins over description, and dc:creator names the author
+    let bridges = &entries[1].article;
+    assert_eq!(bridges.headline(), "Bridges & tunnels: a count");
+    assert_eq!(bridges.author(), Some("Al Roker"));
+    assert_eq!(
+        bridges.content(),
+        "The city has 446 bridges, more than Venice. Nobody is sure who counted."
+    );
+    assert_eq!(entries[1].published, at(1_497_254_400));
+    assert_eq!(
+        entries[1].link.as_deref(),
+        Some("https://example.com/bridges")
+    );
+
+    // Missing fields are empty, and the channel's editor stands in as author
+    let bare = &entries[2];
+    assert_eq!(bare.article.headline(), "");
+    assert_eq!(bare.article.author(), Some("Desk Editor"));
+    assert_eq!(bare.published, None);
+    assert_eq!(bare.link, None);
+
+    let cafe = &entries[3];
+    assert_eq!(
+        cafe.article.headline(),
+        "Caf\u{e9} opens on Carson Street \u{2014} with a bad date"
+    );
+    assert_eq!(cafe.article.content(), "");
+    assert_eq!(cafe.published, None);
+}
+
+#[test]
+fn parses_atom() {
+    let entries = syndication::parse(include_str!("fixtures/atom.xml")).unwrap();
+    assert_eq!(entries.len(), 2);
+
+    let rain = &entries[0];
+    assert_eq!(rain.article.headline(), "Rain again in the forecast");
+    assert_eq!(rain.article.author(), Some("Al Roker"));
+    assert_eq!(rain.article.content(), "Bring an umbrella. Maybe two.");
+    assert_eq!(rain.published, at(1_071_318_569));
+    assert_eq!(rain.link.as_deref(), Some("https://example.org/rain"));
+
+    // Plain text titles keep their angle brackets, the feed's author fills
+    // in, and summary stands in for content
+    let stocks = &entries[1];
+    assert_eq!(stocks.article.headline(), "Stocks close <up> slightly");
+    assert_eq!(stocks.article.author(), Some("Newsroom"));
+    assert_eq!(stocks.article.content(), "Markets were calm & quiet.");
+    assert_eq!(stocks.published, at(1_071_340_202));
+    assert_eq!(stocks.link.as_deref(), Some("https://example.org/stocks"));
+}
+
+#[test]
+fn parses_rss1() {
+    let entries = syndication::parse(include_str!("fixtures/rss1.rdf")).unwrap();
+    let headlines: Vec<&str> = entries.iter().map(|e| e.article.headline()).collect();
+    assert_eq!(headlines, ["First", "Second"]);
+    assert_eq!(entries[0].article.author(), Some("Ada"));
+    assert_eq!(entries[0].published, at(978_307_200));
+    assert_eq!(entries[0].article.summarize(), "First, by Ada ()");
+}
+
+#[test]
+fn rejects_what_is_not_a_feed() {
+    assert_eq!(
+        syndication::parse("<html><body>hi</body></html>"),
+        Err(ParseError::NotAFeed(String::from("html")))
+    );
+    assert!(matches!(
+        syndication::parse("<rss><channel><item><title>cut off"),
+        Ok(entries) if entries.len() == 1
+    ));
+    assert!(matches!(
+        syndication::parse("<rss><!-- cut off"),
+        Err(ParseError::Malformed { .. })
+    ));
+    assert_eq!(syndication::parse("<rss version=\"2.0\"/>"), Ok(Vec::new()));
+}